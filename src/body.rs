@@ -0,0 +1,447 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// A `#` at the start of a line or after whitespace, followed by the characters Obsidian
+/// allows in tags. `# Heading` and `[[Note#Heading]]` don't match.
+static TAG_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|\s)#([\p{L}\p{N}_/\-]+)").unwrap());
+
+static INLINE_CODE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`[^`]*`").unwrap());
+
+static URL_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[a-zA-Z][\w+.-]*://\S*").unwrap());
+
+/// Lines of the note body outside fenced code blocks.
+pub fn prose_lines(body: &str) -> impl Iterator<Item = &str> {
+    let mut fence: Option<&str> = None;
+
+    body.lines().filter(move |line| {
+        let trimmed = line.trim_start();
+
+        match fence {
+            Some(marker) => {
+                if trimmed.starts_with(marker) {
+                    fence = None;
+                }
+                false
+            }
+            None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                fence = Some(&trimmed[..3]);
+                false
+            }
+            None => true,
+        }
+    })
+}
+
+/// Removes inline code spans and URLs from a line, whose contents are never tags.
+fn strip_code_and_urls(line: &str) -> String {
+    let line = INLINE_CODE_REGEX.replace_all(line, " ");
+
+    URL_REGEX.replace_all(&line, " ").into_owned()
+}
+
+/// Extracts the inline `#tags` of a note body, without their `#`, in order of first appearance.
+/// Tags made only of digits, like `#123`, are not tags in Obsidian and are skipped.
+pub fn extract_tags(body: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+
+    for line in prose_lines(body) {
+        for tag in line_tags(line) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+
+    tags
+}
+
+/// Tags of a single line of prose, without their `#`.
+fn line_tags(line: &str) -> Vec<String> {
+    let line = strip_code_and_urls(line);
+
+    TAG_REGEX
+        .captures_iter(&line)
+        .map(|capture| capture[1].trim_end_matches('/').to_string())
+        .filter(|tag| !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()))
+        .collect()
+}
+
+/// A list item with a checkbox, `- [ ] task`, capturing its status character and its text.
+static TASK_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*(?:[-*+]|\d+[.)])\s+\[(.)\]\s*(.*)$").unwrap());
+
+/// Status of a task, from the character in its checkbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// `[ ]`, or any custom status that isn't one of the others, like `[/]` for in progress.
+    Open,
+    /// `[x]` or `[X]`.
+    Done,
+    /// `[-]`.
+    Cancelled,
+    /// `[>]`.
+    Deferred,
+}
+
+#[derive(Debug, Clone)]
+pub struct Task {
+    pub status: TaskStatus,
+    /// Tags in the task's text, without their `#`.
+    pub tags: Vec<String>,
+}
+
+/// Extracts the tasks of a note body, outside code blocks.
+pub fn extract_tasks(body: &str) -> Vec<Task> {
+    prose_lines(body)
+        .filter_map(|line| TASK_REGEX.captures(line))
+        .map(|capture| {
+            let status = match &capture[1] {
+                "x" | "X" => TaskStatus::Done,
+                "-" => TaskStatus::Cancelled,
+                ">" => TaskStatus::Deferred,
+                _ => TaskStatus::Open,
+            };
+
+            let mut tags = line_tags(&capture[2]);
+            tags.sort();
+            tags.dedup();
+
+            Task { status, tags }
+        })
+        .collect()
+}
+
+/// A bracketed inline field, `[key:: value]` or `(key:: value)`, anywhere in a line.
+static BRACKETED_FIELD_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[\[(]([^\[\]()]+?)::([^\[\]()]*)[\])]").unwrap());
+
+/// A full-line inline field, `key:: value`, optionally in a list item or quote.
+static LINE_FIELD_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*(?:[-*+]\s+|>\s*)?([^\[\]()`:]+?)::(.*)$").unwrap());
+
+static DURATION_PART_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\d+(?:\.\d+)?)\s*([a-zA-Z]+)[\s,]*").unwrap());
+
+/// Value of a Dataview inline field, typed the way Dataview reads it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Number(f64),
+    Bool(bool),
+    /// A duration such as `1h 30m`, in seconds.
+    Duration(f64),
+    Text(String),
+}
+
+/// Normalizes an inline field key the way Dataview does: formatting and punctuation are
+/// removed, and it is lower-cased with spaces replaced by `-`, so `**Best Bar**` is `best-bar`.
+fn normalize_field_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace() || *c == '-' || *c == '_')
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join("-")
+        .to_lowercase()
+}
+
+fn duration_unit_seconds(unit: &str) -> Option<f64> {
+    let seconds = match unit.to_lowercase().as_str() {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1.0,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60.0,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3_600.0,
+        "d" | "day" | "days" => 86_400.0,
+        "w" | "wk" | "wks" | "week" | "weeks" => 604_800.0,
+        "mo" | "month" | "months" => 2_592_000.0,
+        "y" | "yr" | "yrs" | "year" | "years" => 31_536_000.0,
+        _ => return None,
+    };
+
+    Some(seconds)
+}
+
+/// Parses durations such as `7 hours`, `1h 30m` or `1 hour, 30 minutes` into seconds.
+fn parse_duration(value: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    let mut parsed_len = 0;
+
+    for capture in DURATION_PART_REGEX.captures_iter(value) {
+        let part = capture.get(0)?;
+
+        if part.start() != parsed_len {
+            return None;
+        }
+
+        seconds += capture[1].parse::<f64>().ok()? * duration_unit_seconds(&capture[2])?;
+        parsed_len = part.end();
+    }
+
+    (parsed_len > 0 && parsed_len == value.len()).then_some(seconds)
+}
+
+fn parse_field_value(value: &str) -> FieldValue {
+    let is_number = value.chars().any(|c| c.is_ascii_digit())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'));
+
+    if let Some(number) = is_number.then(|| value.parse::<f64>().ok()).flatten() {
+        FieldValue::Number(number)
+    } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+        FieldValue::Bool(value.eq_ignore_ascii_case("true"))
+    } else if let Some(seconds) = parse_duration(value) {
+        FieldValue::Duration(seconds)
+    } else {
+        FieldValue::Text(value.to_string())
+    }
+}
+
+/// Extracts the Dataview inline fields (`key:: value`, `[key:: value]` and `(key:: value)`)
+/// of a note body. Only the first value of a key is kept, and fields without a value are skipped.
+pub fn extract_inline_fields(body: &str) -> Vec<(String, FieldValue)> {
+    let mut fields: Vec<(String, FieldValue)> = Vec::new();
+
+    for line in prose_lines(body) {
+        let line = INLINE_CODE_REGEX.replace_all(line, " ");
+
+        let bracketed = BRACKETED_FIELD_REGEX
+            .captures_iter(&line)
+            .map(|capture| (capture[1].to_string(), capture[2].to_string()));
+
+        let full_line = LINE_FIELD_REGEX
+            .captures(&line)
+            .map(|capture| (capture[1].to_string(), capture[2].to_string()));
+
+        for (key, value) in bracketed.chain(full_line) {
+            let key = normalize_field_key(&key);
+            let value = value.trim();
+
+            if key.is_empty() || value.is_empty() || fields.iter().any(|(k, _)| *k == key) {
+                continue;
+            }
+
+            fields.push((key, parse_field_value(value)));
+        }
+    }
+
+    fields
+}
+
+static HEADING_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^#{1,6}(?:\s|$)").unwrap());
+
+/// A wikilink, `[[Note]]`, or an embed, `![[Note]]`, capturing the `!`.
+static WIKILINK_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(!?)\[\[[^\[\]]+\]\]").unwrap());
+
+/// Counts of what a note body is made of.
+#[derive(Debug, Clone, Default)]
+pub struct WritingStats {
+    pub words: u32,
+    /// Characters other than line breaks.
+    pub characters: u32,
+    pub headings: u32,
+    pub wikilinks: u32,
+    pub embeds: u32,
+    pub external_links: u32,
+}
+
+/// Counts the words and characters of a note body, and its headings and links outside code.
+pub fn writing_stats(body: &str) -> WritingStats {
+    let count = |n: usize| u32::try_from(n).unwrap_or(u32::MAX);
+
+    let mut stats = WritingStats {
+        words: count(body.split_whitespace().count()),
+        characters: count(body.chars().filter(|c| !matches!(c, '\n' | '\r')).count()),
+        ..WritingStats::default()
+    };
+
+    for line in prose_lines(body) {
+        let line = INLINE_CODE_REGEX.replace_all(line, " ");
+
+        if HEADING_REGEX.is_match(&line) {
+            stats.headings += 1;
+        }
+
+        for capture in WIKILINK_REGEX.captures_iter(&line) {
+            if capture[1].is_empty() {
+                stats.wikilinks += 1;
+            } else {
+                stats.embeds += 1;
+            }
+        }
+
+        stats.external_links += count(URL_REGEX.find_iter(&line).count());
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, value: FieldValue) -> (String, FieldValue) {
+        (key.to_string(), value)
+    }
+
+    #[test]
+    fn extracts_tags_in_order_of_first_appearance() {
+        let body = "#work on the #project/alpha, then #work again\n- [ ] call #mum/\n";
+
+        assert_eq!(extract_tags(body), vec!["work", "project/alpha", "mum"]);
+    }
+
+    #[test]
+    fn skips_what_obsidian_does_not_read_as_tags() {
+        let body = "# Heading\n## Sub #heading-tag\nissue#12 and #123, [[Note#Section]]\n";
+
+        assert_eq!(extract_tags(body), vec!["heading-tag"]);
+    }
+
+    #[test]
+    fn skips_tags_in_code_and_urls() {
+        let body = "`#inline` #real\n```\n#fenced\n```\n~~~\n#tilde\n~~~\n\
+                    https://example.com/#anchor #after\n";
+
+        assert_eq!(extract_tags(body), vec!["real", "after"]);
+    }
+
+    #[test]
+    fn reads_tags_in_any_script() {
+        assert_eq!(extract_tags("#日記 and #café_2"), vec!["日記", "café_2"]);
+    }
+
+    fn statuses(tasks: &[Task]) -> Vec<TaskStatus> {
+        tasks.iter().map(|task| task.status).collect()
+    }
+
+    #[test]
+    fn reads_task_statuses() {
+        let body = "- [ ] open\n- [x] done\n- [X] also done\n- [-] cancelled\n\
+                    - [>] deferred\n- [/] in progress\n";
+
+        assert_eq!(
+            statuses(&extract_tasks(body)),
+            vec![
+                TaskStatus::Open,
+                TaskStatus::Done,
+                TaskStatus::Done,
+                TaskStatus::Cancelled,
+                TaskStatus::Deferred,
+                TaskStatus::Open,
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_tasks_in_any_list() {
+        let body = "* [x] star\n+ [ ] plus\n  - [ ] nested\n1. [x] numbered\n2) [ ] paren\n";
+
+        assert_eq!(extract_tasks(body).len(), 5);
+    }
+
+    #[test]
+    fn skips_what_is_not_a_task() {
+        let body = "[ ] no list\n- [] no status\n-[ ] no space\n- plain item\n\
+                    ```\n- [ ] fenced\n```\n";
+
+        assert!(extract_tasks(body).is_empty());
+    }
+
+    #[test]
+    fn reads_the_tags_of_tasks() {
+        let tasks = extract_tasks("- [ ] call #mum about #work #mum `#code`\n- [x] rest\n");
+
+        assert_eq!(tasks[0].tags, vec!["mum", "work"]);
+        assert!(tasks[1].tags.is_empty());
+    }
+
+    #[test]
+    fn normalizes_field_keys() {
+        assert_eq!(normalize_field_key("**Best Bar**"), "best-bar");
+        assert_eq!(normalize_field_key("Slept  Well"), "slept-well");
+        assert_eq!(normalize_field_key("_mood_"), "_mood_");
+        assert_eq!(normalize_field_key("Ünïcode Key!"), "ünïcode-key");
+        assert_eq!(normalize_field_key("**"), "");
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("7 hours"), Some(25_200.0));
+        assert_eq!(parse_duration("1h 30m"), Some(5_400.0));
+        assert_eq!(parse_duration("1 hour, 30 minutes"), Some(5_400.0));
+        assert_eq!(parse_duration("1.5h"), Some(5_400.0));
+        assert_eq!(parse_duration("2 days"), Some(172_800.0));
+        assert_eq!(parse_duration("1h and 30m"), None);
+        assert_eq!(parse_duration("3 apples"), None);
+        assert_eq!(parse_duration("about 1h"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn parses_field_values() {
+        assert_eq!(parse_field_value("72.4"), FieldValue::Number(72.4));
+        assert_eq!(parse_field_value("-3"), FieldValue::Number(-3.0));
+        assert_eq!(parse_field_value("True"), FieldValue::Bool(true));
+        assert_eq!(parse_field_value("false"), FieldValue::Bool(false));
+        assert_eq!(parse_field_value("7h 30m"), FieldValue::Duration(27_000.0));
+        assert_eq!(
+            parse_field_value("3/5"),
+            FieldValue::Text("3/5".to_string())
+        );
+        assert_eq!(
+            parse_field_value("2024-03-05"),
+            FieldValue::Text("2024-03-05".to_string())
+        );
+        assert_eq!(parse_field_value("e"), FieldValue::Text("e".to_string()));
+    }
+
+    #[test]
+    fn extracts_full_line_fields() {
+        let body = "Weight:: 72.4\n- Mood:: good\n> **Slept Well**:: true\nNot a field: 3\n";
+
+        assert_eq!(
+            extract_inline_fields(body),
+            vec![
+                field("weight", FieldValue::Number(72.4)),
+                field("mood", FieldValue::Text("good".to_string())),
+                field("slept-well", FieldValue::Bool(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn extracts_bracketed_fields() {
+        let body = "Today [mood:: 8] and (sleep:: 7h 30m), see [[Note]] (aside).\n";
+
+        assert_eq!(
+            extract_inline_fields(body),
+            vec![
+                field("mood", FieldValue::Number(8.0)),
+                field("sleep", FieldValue::Duration(27_000.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_first_value_of_a_key() {
+        let body = "mood:: 8\n[Mood:: 3]\n";
+
+        assert_eq!(
+            extract_inline_fields(body),
+            vec![field("mood", FieldValue::Number(8.0))]
+        );
+    }
+
+    #[test]
+    fn skips_fields_in_code_and_without_values() {
+        let body = "`code:: 1` [inline:: 2]\n```\nfenced:: 3\n```\nempty::\n[blank:: ]\n";
+
+        assert_eq!(
+            extract_inline_fields(body),
+            vec![field("inline", FieldValue::Number(2.0))]
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use std::{any::TypeId, collections::HashMap};
+
+use chrono::NaiveDate;
+use clap::{Arg, Args, Parser, Subcommand};
+
+use crate::config::{
+    BATCH_SIZE_VAR_HANDLE, BODY_TAGS_VAR_HANDLE, BOOLEAN_PROPERTIES_VAR_HANDLE,
+    CONFIG_FILE_VAR_HANDLE, DATE_FORMAT_VAR_HANDLE, DATE_MATCH_VAR_HANDLE,
+    DATE_PROPERTY_VAR_HANDLE, DB_BUCKET_VAR_HANDLE, DB_CA_CERT_VAR_HANDLE, DB_HOST_VAR_HANDLE,
+    DB_MEASUREMENT_VAR_HANDLE, DB_NAME_VAR_HANDLE, DB_ORG_VAR_HANDLE, DB_PASSWORD_VAR_HANDLE,
+    DB_PORT_VAR_HANDLE, DB_TLS_SKIP_VERIFY_VAR_HANDLE, DB_TLS_VAR_HANDLE, DB_TOKEN_VAR_HANDLE,
+    DB_USERNAME_VAR_HANDLE, DB_VERSION_VAR_HANDLE, INLINE_FIELDS_VAR_HANDLE,
+    MONTHLY_DATE_FORMAT_VAR_HANDLE, MONTHLY_NOTES_DIR_VAR_HANDLE, NOTES_DIR_VAR_HANDLE,
+    NUMERIC_PROPERTIES_VAR_HANDLE, QUARTERLY_DATE_FORMAT_VAR_HANDLE,
+    QUARTERLY_NOTES_DIR_VAR_HANDLE, RECONCILE_VAR_HANDLE, STATE_FILE_VAR_HANDLE,
+    TAG_ROLLUPS_VAR_HANDLE, TASKS_VAR_HANDLE, TASK_TAGS_VAR_HANDLE, TIMESTAMP_MODE_VAR_HANDLE,
+    TIMEZONE_VAR_HANDLE, VAULT_PATH_VAR_HANDLE, WEEKLY_DATE_FORMAT_VAR_HANDLE,
+    WEEKLY_NOTES_DIR_VAR_HANDLE, WRITE_RETRIES_VAR_HANDLE, WRITING_STATS_VAR_HANDLE,
+    YEARLY_DATE_FORMAT_VAR_HANDLE, YEARLY_NOTES_DIR_VAR_HANDLE,
+};
+
+/// Parse the frontmatter of Obsidian daily notes and push tags to InfluxDB.
+///
+/// Every setting can be given in a TOML config file with the flag name in snake case
+/// (e.g. `db_host`), or as an env var of the same name in upper case (e.g. `DB_HOST`).
+/// Env vars take precedence over the config file, and flags over both.
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Push notes that are new (or edited, with a state file) since the last run [default]
+    Sync,
+    /// Push every note in a date range, whether it was pushed before or not
+    Backfill {
+        /// First date to push, as YYYY-MM-DD
+        #[arg(long)]
+        from: NaiveDate,
+        /// Last date to push, as YYYY-MM-DD
+        #[arg(long)]
+        to: NaiveDate,
+        /// Delete every point already in the range before pushing
+        #[arg(long, conflicts_with = "skip_existing")]
+        overwrite: bool,
+        /// Skip days that already have points
+        #[arg(long)]
+        skip_existing: bool,
+    },
+    /// Print the points a sync would write, and a summary, without writing anything
+    DryRun,
+    /// Delete points for tags that were removed from notes
+    Reconcile,
+    /// Show the latest point in InfluxDB and what the state file records
+    Status,
+    /// Delete the state file so the next sync starts from scratch
+    Reset,
+}
+
+/// Settings given on the command line, overriding their env vars.
+#[derive(Args, Default)]
+#[command(mut_args = switch_arg)]
+pub struct ConfigOverrides {
+    /// Path to a TOML config file
+    #[arg(long = "config", global = true)]
+    config_file: Option<String>,
+    /// InfluxDB host
+    #[arg(long, global = true)]
+    db_host: Option<String>,
+    /// InfluxDB port
+    #[arg(long, global = true)]
+    db_port: Option<String>,
+    /// InfluxDB API version, 1 or 2
+    #[arg(long, global = true)]
+    db_version: Option<String>,
+    /// InfluxDB database name (v1)
+    #[arg(long, global = true)]
+    db_name: Option<String>,
+    /// InfluxDB organization (v2)
+    #[arg(long, global = true)]
+    db_org: Option<String>,
+    /// InfluxDB bucket (v2)
+    #[arg(long, global = true)]
+    db_bucket: Option<String>,
+    /// InfluxDB API token (v2, or the v1 compatibility API)
+    #[arg(long, global = true)]
+    db_token: Option<String>,
+    /// InfluxDB username (v1)
+    #[arg(long, global = true)]
+    db_username: Option<String>,
+    /// InfluxDB password (v1)
+    #[arg(long, global = true)]
+    db_password: Option<String>,
+    /// Connect to InfluxDB over HTTPS
+    #[arg(long, global = true)]
+    db_tls: Option<bool>,
+    /// PEM file of CA certificates to trust for InfluxDB's certificate
+    #[arg(long, global = true)]
+    db_ca_cert: Option<String>,
+    /// Accept InfluxDB's certificate without verifying it
+    #[arg(long, global = true)]
+    db_tls_skip_verify: Option<bool>,
+    /// Measurement the tags are written to
+    #[arg(long, global = true)]
+    db_measurement: Option<String>,
+    /// Path to the JSON file recording which notes were pushed
+    #[arg(long, global = true)]
+    state_file: Option<String>,
+    /// Most points written in a single request [default: 5000]
+    #[arg(long, global = true)]
+    batch_size: Option<String>,
+    /// Times a write failing with a server error or timeout is retried [default: 3]
+    #[arg(long, global = true)]
+    write_retries: Option<String>,
+    /// Delete points for tags removed from notes after syncing
+    #[arg(long, global = true)]
+    reconcile: Option<bool>,
+    /// Also read inline #tags from the body of notes
+    #[arg(long, global = true)]
+    body_tags: Option<bool>,
+    /// Also write a rollup point for every ancestor of nested tags
+    #[arg(long, global = true)]
+    tag_rollups: Option<bool>,
+    /// Numeric frontmatter properties written as fields, `*` for all or a comma-separated list
+    #[arg(long, global = true)]
+    numeric_properties: Option<String>,
+    /// Boolean frontmatter properties written as habit points, `*` for all or a comma-separated list
+    #[arg(long, global = true)]
+    boolean_properties: Option<String>,
+    /// Write Dataview inline fields (`key:: value`) from the body of notes as fields
+    #[arg(long, global = true)]
+    inline_fields: Option<bool>,
+    /// Write the number of tasks in each status for every note
+    #[arg(long, global = true)]
+    tasks: Option<bool>,
+    /// Write the number of tasks in each status per tag found in the tasks
+    #[arg(long, global = true)]
+    task_tags: Option<bool>,
+    /// Write the number of words, characters, headings and links of every note
+    #[arg(long, global = true)]
+    writing_stats: Option<bool>,
+    /// Date format of daily note names, in Moment.js tokens (`YYYY-MM-DD`) or strftime (`%Y-%m-%d`),
+    /// read from the vault's daily notes settings by default
+    #[arg(long, global = true)]
+    date_format: Option<String>,
+    /// How much of the note name the date format matches: exact, prefix or suffix
+    #[arg(long, global = true)]
+    date_match: Option<String>,
+    /// Frontmatter properties that date notes, like `date` or `created`, before their file name
+    #[arg(long, global = true)]
+    date_property: Option<String>,
+    /// IANA timezone that notes are dated in, like `Europe/Berlin` [default: UTC]
+    #[arg(long, global = true)]
+    timezone: Option<String>,
+    /// Time within their day that points of notes are written at: midnight, note_time or hash
+    /// [default: note_time]
+    #[arg(long, global = true)]
+    timestamp_mode: Option<String>,
+    /// Directory of daily notes to be parsed, relative to the vault, read from the vault's daily
+    /// notes settings by default
+    #[arg(long, global = true)]
+    notes_dir: Option<String>,
+    /// Directory of weekly notes to be parsed, relative to the vault
+    #[arg(long, global = true)]
+    weekly_notes_dir: Option<String>,
+    /// Date format of weekly note names, in Moment.js tokens or strftime
+    #[arg(long, global = true)]
+    weekly_date_format: Option<String>,
+    /// Directory of monthly notes to be parsed, relative to the vault
+    #[arg(long, global = true)]
+    monthly_notes_dir: Option<String>,
+    /// Date format of monthly note names, in Moment.js tokens or strftime
+    #[arg(long, global = true)]
+    monthly_date_format: Option<String>,
+    /// Directory of quarterly notes to be parsed, relative to the vault
+    #[arg(long, global = true)]
+    quarterly_notes_dir: Option<String>,
+    /// Date format of quarterly note names, in Moment.js tokens or strftime
+    #[arg(long, global = true)]
+    quarterly_date_format: Option<String>,
+    /// Directory of yearly notes to be parsed, relative to the vault
+    #[arg(long, global = true)]
+    yearly_notes_dir: Option<String>,
+    /// Date format of yearly note names, in Moment.js tokens or strftime
+    #[arg(long, global = true)]
+    yearly_date_format: Option<String>,
+    /// Path to the Obsidian vault
+    #[arg(long, global = true)]
+    vault_path: Option<String>,
+}
+
+/// Makes every `Option<bool>` setting a switch: a bare `--reconcile` turns it on, and
+/// `--reconcile=false` turns it off over its env var. Values need the `=`, so that
+/// `--reconcile sync` reads `sync` as the command.
+fn switch_arg(arg: Arg) -> Arg {
+    if arg.get_value_parser().type_id() != TypeId::of::<bool>() {
+        return arg;
+    }
+
+    arg.num_args(0..=1)
+        .require_equals(true)
+        .default_missing_value("true")
+}
+
+impl ConfigOverrides {
+    /// Returns the given settings keyed by the env var they override.
+    pub fn to_vars(&self) -> HashMap<&'static str, String> {
+        let flags = [
+            (CONFIG_FILE_VAR_HANDLE, &self.config_file),
+            (DB_HOST_VAR_HANDLE, &self.db_host),
+            (DB_PORT_VAR_HANDLE, &self.db_port),
+            (DB_VERSION_VAR_HANDLE, &self.db_version),
+            (DB_NAME_VAR_HANDLE, &self.db_name),
+            (DB_ORG_VAR_HANDLE, &self.db_org),
+            (DB_BUCKET_VAR_HANDLE, &self.db_bucket),
+            (DB_TOKEN_VAR_HANDLE, &self.db_token),
+            (DB_USERNAME_VAR_HANDLE, &self.db_username),
+            (DB_PASSWORD_VAR_HANDLE, &self.db_password),
+            (DB_CA_CERT_VAR_HANDLE, &self.db_ca_cert),
+            (DB_MEASUREMENT_VAR_HANDLE, &self.db_measurement),
+            (STATE_FILE_VAR_HANDLE, &self.state_file),
+            (BATCH_SIZE_VAR_HANDLE, &self.batch_size),
+            (WRITE_RETRIES_VAR_HANDLE, &self.write_retries),
+            (NUMERIC_PROPERTIES_VAR_HANDLE, &self.numeric_properties),
+            (BOOLEAN_PROPERTIES_VAR_HANDLE, &self.boolean_properties),
+            (DATE_FORMAT_VAR_HANDLE, &self.date_format),
+            (DATE_MATCH_VAR_HANDLE, &self.date_match),
+            (DATE_PROPERTY_VAR_HANDLE, &self.date_property),
+            (TIMEZONE_VAR_HANDLE, &self.timezone),
+            (TIMESTAMP_MODE_VAR_HANDLE, &self.timestamp_mode),
+            (NOTES_DIR_VAR_HANDLE, &self.notes_dir),
+            (WEEKLY_NOTES_DIR_VAR_HANDLE, &self.weekly_notes_dir),
+            (WEEKLY_DATE_FORMAT_VAR_HANDLE, &self.weekly_date_format),
+            (MONTHLY_NOTES_DIR_VAR_HANDLE, &self.monthly_notes_dir),
+            (MONTHLY_DATE_FORMAT_VAR_HANDLE, &self.monthly_date_format),
+            (QUARTERLY_NOTES_DIR_VAR_HANDLE, &self.quarterly_notes_dir),
+            (
+                QUARTERLY_DATE_FORMAT_VAR_HANDLE,
+                &self.quarterly_date_format,
+            ),
+            (YEARLY_NOTES_DIR_VAR_HANDLE, &self.yearly_notes_dir),
+            (YEARLY_DATE_FORMAT_VAR_HANDLE, &self.yearly_date_format),
+            (VAULT_PATH_VAR_HANDLE, &self.vault_path),
+        ];
+
+        let mut vars: HashMap<&'static str, String> = flags
+            .into_iter()
+            .filter_map(|(handle, value)| value.clone().map(|value| (handle, value)))
+            .collect();
+
+        let switches = [
+            (DB_TLS_VAR_HANDLE, self.db_tls),
+            (DB_TLS_SKIP_VERIFY_VAR_HANDLE, self.db_tls_skip_verify),
+            (RECONCILE_VAR_HANDLE, self.reconcile),
+            (BODY_TAGS_VAR_HANDLE, self.body_tags),
+            (TAG_ROLLUPS_VAR_HANDLE, self.tag_rollups),
+            (INLINE_FIELDS_VAR_HANDLE, self.inline_fields),
+            (TASKS_VAR_HANDLE, self.tasks),
+            (TASK_TAGS_VAR_HANDLE, self.task_tags),
+            (WRITING_STATS_VAR_HANDLE, self.writing_stats),
+        ];
+
+        switches
+            .into_iter()
+            .filter_map(|(handle, enabled)| enabled.map(|enabled| (handle, enabled)))
+            .for_each(|(handle, enabled)| {
+                vars.insert(handle, enabled.to_string());
+            });
+
+        vars
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from([&["obsidian_to_influx"], args].concat()).unwrap()
+    }
+
+    #[test]
+    fn turns_switches_on_without_a_value() {
+        let cli = parse(&["--reconcile"]);
+
+        assert_eq!(cli.overrides.reconcile, Some(true));
+        assert!(cli.command.is_none());
+    }
+
+    #[test]
+    fn turns_switches_off_with_a_value() {
+        assert_eq!(
+            parse(&["--reconcile=false"]).overrides.reconcile,
+            Some(false)
+        );
+        assert_eq!(parse(&["--reconcile=true"]).overrides.reconcile, Some(true));
+        assert_eq!(
+            parse(&["sync", "--reconcile=false"]).overrides.reconcile,
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn reads_the_command_after_a_switch() {
+        let cli = parse(&["--reconcile", "sync"]);
+
+        assert_eq!(cli.overrides.reconcile, Some(true));
+        assert!(matches!(cli.command, Some(Command::Sync)));
+    }
+
+    #[test]
+    fn leaves_switches_unset_when_not_given() {
+        assert_eq!(parse(&["sync"]).overrides.reconcile, None);
+    }
+
+    #[test]
+    fn keeps_values_of_other_settings() {
+        let cli = parse(&["--db-host", "localhost", "sync"]);
+
+        assert_eq!(cli.overrides.db_host.as_deref(), Some("localhost"));
+        assert!(matches!(cli.command, Some(Command::Sync)));
+    }
+}
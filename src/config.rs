@@ -0,0 +1,647 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Error};
+use chrono_tz::Tz;
+
+use crate::{
+    date_format::{DateFormat, DateMatch},
+    obsidian::{read_note_settings, NoteSettings},
+    period::Period,
+};
+
+pub const CONFIG_FILE_VAR_HANDLE: &str = "CONFIG_FILE";
+pub const DB_HOST_VAR_HANDLE: &str = "DB_HOST";
+pub const DB_NAME_VAR_HANDLE: &str = "DB_NAME";
+pub const DB_PORT_VAR_HANDLE: &str = "DB_PORT";
+pub const DB_VERSION_VAR_HANDLE: &str = "DB_VERSION";
+pub const DB_ORG_VAR_HANDLE: &str = "DB_ORG";
+pub const DB_BUCKET_VAR_HANDLE: &str = "DB_BUCKET";
+pub const DB_TOKEN_VAR_HANDLE: &str = "DB_TOKEN";
+pub const DB_USERNAME_VAR_HANDLE: &str = "DB_USERNAME";
+pub const DB_PASSWORD_VAR_HANDLE: &str = "DB_PASSWORD";
+pub const DB_TLS_VAR_HANDLE: &str = "DB_TLS";
+pub const DB_CA_CERT_VAR_HANDLE: &str = "DB_CA_CERT";
+pub const DB_TLS_SKIP_VERIFY_VAR_HANDLE: &str = "DB_TLS_SKIP_VERIFY";
+pub const DB_MEASUREMENT_VAR_HANDLE: &str = "DB_MEASUREMENT";
+pub const STATE_FILE_VAR_HANDLE: &str = "STATE_FILE";
+pub const BATCH_SIZE_VAR_HANDLE: &str = "BATCH_SIZE";
+pub const WRITE_RETRIES_VAR_HANDLE: &str = "WRITE_RETRIES";
+pub const RECONCILE_VAR_HANDLE: &str = "RECONCILE";
+pub const BODY_TAGS_VAR_HANDLE: &str = "BODY_TAGS";
+pub const INLINE_FIELDS_VAR_HANDLE: &str = "INLINE_FIELDS";
+pub const TASKS_VAR_HANDLE: &str = "TASKS";
+pub const TASK_TAGS_VAR_HANDLE: &str = "TASK_TAGS";
+pub const WRITING_STATS_VAR_HANDLE: &str = "WRITING_STATS";
+pub const TAG_ROLLUPS_VAR_HANDLE: &str = "TAG_ROLLUPS";
+pub const NUMERIC_PROPERTIES_VAR_HANDLE: &str = "NUMERIC_PROPERTIES";
+pub const BOOLEAN_PROPERTIES_VAR_HANDLE: &str = "BOOLEAN_PROPERTIES";
+pub const DATE_FORMAT_VAR_HANDLE: &str = "DATE_FORMAT";
+pub const DATE_MATCH_VAR_HANDLE: &str = "DATE_MATCH";
+pub const DATE_PROPERTY_VAR_HANDLE: &str = "DATE_PROPERTY";
+pub const TIMEZONE_VAR_HANDLE: &str = "TIMEZONE";
+pub const TIMESTAMP_MODE_VAR_HANDLE: &str = "TIMESTAMP_MODE";
+pub const NOTES_DIR_VAR_HANDLE: &str = "NOTES_DIR";
+pub const WEEKLY_NOTES_DIR_VAR_HANDLE: &str = "WEEKLY_NOTES_DIR";
+pub const WEEKLY_DATE_FORMAT_VAR_HANDLE: &str = "WEEKLY_DATE_FORMAT";
+pub const MONTHLY_NOTES_DIR_VAR_HANDLE: &str = "MONTHLY_NOTES_DIR";
+pub const MONTHLY_DATE_FORMAT_VAR_HANDLE: &str = "MONTHLY_DATE_FORMAT";
+pub const QUARTERLY_NOTES_DIR_VAR_HANDLE: &str = "QUARTERLY_NOTES_DIR";
+pub const QUARTERLY_DATE_FORMAT_VAR_HANDLE: &str = "QUARTERLY_DATE_FORMAT";
+pub const YEARLY_NOTES_DIR_VAR_HANDLE: &str = "YEARLY_NOTES_DIR";
+pub const YEARLY_DATE_FORMAT_VAR_HANDLE: &str = "YEARLY_DATE_FORMAT";
+pub const VAULT_PATH_VAR_HANDLE: &str = "VAULT_PATH";
+
+const DEFAULT_BATCH_SIZE: usize = 5000;
+const DEFAULT_WRITE_RETRIES: u32 = 3;
+
+/// Every setting, which can be given in the config file, as an env var or as a flag.
+const SETTINGS: &[&str] = &[
+    DB_HOST_VAR_HANDLE,
+    DB_NAME_VAR_HANDLE,
+    DB_PORT_VAR_HANDLE,
+    DB_VERSION_VAR_HANDLE,
+    DB_ORG_VAR_HANDLE,
+    DB_BUCKET_VAR_HANDLE,
+    DB_TOKEN_VAR_HANDLE,
+    DB_USERNAME_VAR_HANDLE,
+    DB_PASSWORD_VAR_HANDLE,
+    DB_TLS_VAR_HANDLE,
+    DB_CA_CERT_VAR_HANDLE,
+    DB_TLS_SKIP_VERIFY_VAR_HANDLE,
+    DB_MEASUREMENT_VAR_HANDLE,
+    STATE_FILE_VAR_HANDLE,
+    BATCH_SIZE_VAR_HANDLE,
+    WRITE_RETRIES_VAR_HANDLE,
+    RECONCILE_VAR_HANDLE,
+    BODY_TAGS_VAR_HANDLE,
+    TAG_ROLLUPS_VAR_HANDLE,
+    INLINE_FIELDS_VAR_HANDLE,
+    TASKS_VAR_HANDLE,
+    TASK_TAGS_VAR_HANDLE,
+    WRITING_STATS_VAR_HANDLE,
+    NUMERIC_PROPERTIES_VAR_HANDLE,
+    BOOLEAN_PROPERTIES_VAR_HANDLE,
+    DATE_FORMAT_VAR_HANDLE,
+    DATE_MATCH_VAR_HANDLE,
+    DATE_PROPERTY_VAR_HANDLE,
+    TIMEZONE_VAR_HANDLE,
+    TIMESTAMP_MODE_VAR_HANDLE,
+    NOTES_DIR_VAR_HANDLE,
+    WEEKLY_NOTES_DIR_VAR_HANDLE,
+    WEEKLY_DATE_FORMAT_VAR_HANDLE,
+    MONTHLY_NOTES_DIR_VAR_HANDLE,
+    MONTHLY_DATE_FORMAT_VAR_HANDLE,
+    QUARTERLY_NOTES_DIR_VAR_HANDLE,
+    QUARTERLY_DATE_FORMAT_VAR_HANDLE,
+    YEARLY_NOTES_DIR_VAR_HANDLE,
+    YEARLY_DATE_FORMAT_VAR_HANDLE,
+    VAULT_PATH_VAR_HANDLE,
+];
+
+/// How requests to InfluxDB 1.x are authenticated.
+#[derive(Clone)]
+pub enum V1Auth {
+    None,
+    /// A user of an InfluxDB with authentication enabled.
+    Basic {
+        username: String,
+        password: String,
+    },
+    /// An API token, for the 1.x compatibility API of InfluxDB 2.x.
+    Token(String),
+}
+
+/// Which InfluxDB API the notes are written to.
+pub enum DbTarget {
+    /// InfluxDB 1.x, writing to a database through `/write` and reading with InfluxQL.
+    V1 { db_name: String, auth: V1Auth },
+    /// InfluxDB 2.x, writing to a bucket through `/api/v2/write` and reading with Flux.
+    V2 {
+        org: String,
+        bucket: String,
+        token: String,
+    },
+}
+
+/// Which frontmatter properties are picked up, given as `*` for all of them or a
+/// comma-separated list of keys.
+pub enum PropertySelection {
+    None,
+    All,
+    Only(Vec<String>),
+}
+
+impl PropertySelection {
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, Self::None)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        match self {
+            Self::None => false,
+            Self::All => true,
+            Self::Only(keys) => keys.iter().any(|k| k == key),
+        }
+    }
+}
+
+/// Which time within its day a note's points are written at. Every tag is its own series, so
+/// a note's points never collide with each other, and writing the same note again overwrites
+/// its points rather than adding to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampMode {
+    /// The start of the note's day.
+    Midnight,
+    /// The time the note is dated with, or else the start of its day.
+    NoteTime,
+    /// The note's time offset by less than a second, derived from a hash of its path, so that
+    /// several notes of the same day don't overwrite each other's points.
+    Hash,
+}
+
+impl TimestampMode {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "midnight" => Some(Self::Midnight),
+            "note_time" => Some(Self::NoteTime),
+            "hash" => Some(Self::Hash),
+            _ => None,
+        }
+    }
+}
+
+/// Where the notes of a period are kept, relative to the vault, and how they are named.
+pub struct PeriodicNotes {
+    pub period: Period,
+    pub notes_dir: String,
+    pub date_format: DateFormat,
+}
+
+pub struct Config {
+    pub db_host: String,
+    pub db_port: String,
+    pub db_target: DbTarget,
+    /// Whether InfluxDB is reached over HTTPS.
+    pub db_tls: bool,
+    /// PEM file of certificates to trust besides the usual ones, for self-signed certificates.
+    pub db_ca_cert: Option<PathBuf>,
+    /// Accept any certificate, without verifying it.
+    pub db_tls_skip_verify: bool,
+    pub measurement: String,
+    pub state_file: Option<PathBuf>,
+    /// Most points written in a single request.
+    pub batch_size: usize,
+    /// Times a write failing with a server error or timeout is retried.
+    pub write_retries: u32,
+    pub reconcile: bool,
+    pub body_tags: bool,
+    pub tag_rollups: bool,
+    pub numeric_properties: PropertySelection,
+    pub boolean_properties: PropertySelection,
+    pub inline_fields: bool,
+    pub tasks: bool,
+    pub task_tags: bool,
+    pub writing_stats: bool,
+    /// Frontmatter properties that daily notes are dated by, in order of preference, before
+    /// falling back to their name.
+    pub date_properties: Vec<String>,
+    /// Timezone that notes are dated in, for their timestamps and for when a day is over.
+    pub timezone: Tz,
+    pub timestamp_mode: TimestampMode,
+    /// Daily notes, followed by the notes of every other period in use.
+    pub periodic_notes: Vec<PeriodicNotes>,
+    pub vault_path: String,
+}
+
+/// Reads a setting from its env var, or from the file named by `<handle>_FILE`, as with Docker
+/// secrets, trimming the file's surrounding whitespace. Returns `None` when neither is set, and
+/// fails when both are, so that a stale value can't shadow the secret.
+fn get_env_var(handle: &str) -> Result<Option<String>, Error> {
+    let file_handle = format!("{handle}_FILE");
+
+    let path = match (env::var(handle), env::var(&file_handle)) {
+        (Ok(_), Ok(_)) => {
+            return Err(anyhow!(
+                "Both {handle} and {file_handle} are set, expected only one of them"
+            ))
+        }
+        (Ok(value), Err(_)) => return Ok(Some(value)),
+        (Err(_), Ok(path)) => path,
+        (Err(_), Err(_)) => return Ok(None),
+    };
+
+    let contents = fs::read_to_string(&path)
+        .context(format!("Could not read {path} given by {file_handle}"))?;
+
+    Ok(Some(contents.trim().to_string()))
+}
+
+/// Describes a setting by every name it can be given with.
+fn describe(handle: &str) -> String {
+    let key = handle.to_lowercase();
+
+    format!(
+        "{handle} (`{key}` in the config file, --{})",
+        key.replace('_', "-")
+    )
+}
+
+/// Flattens a config file value into the same form as an env var: lists are comma-separated
+/// and tables are comma-separated `key=value` pairs.
+fn toml_value_to_string(value: toml::Value) -> String {
+    match value {
+        toml::Value::String(value) => value,
+        toml::Value::Array(values) => values
+            .into_iter()
+            .map(toml_value_to_string)
+            .collect::<Vec<String>>()
+            .join(","),
+        toml::Value::Table(table) => table
+            .into_iter()
+            .map(|(key, value)| format!("{key}={}", toml_value_to_string(value)))
+            .collect::<Vec<String>>()
+            .join(","),
+        other => other.to_string(),
+    }
+}
+
+fn read_config_file(path: &Path) -> Result<toml::Table, Error> {
+    let contents = fs::read_to_string(path)
+        .context(format!("Could not read config file {:?}", path.as_os_str()))?;
+
+    toml::from_str(&contents).context(format!(
+        "Could not parse config file {:?}",
+        path.as_os_str()
+    ))
+}
+
+/// Settings looked up by their env var handle, layered so that the config file is overridden
+/// by env vars, which are overridden by flags. Missing and invalid settings are collected so
+/// they can all be reported at once.
+struct Vars {
+    values: HashMap<&'static str, String>,
+    /// Settings whose env var couldn't be read, already reported, so not reported as missing.
+    unreadable: HashSet<&'static str>,
+    errors: Vec<String>,
+}
+
+impl Vars {
+    fn load(overrides: HashMap<&'static str, String>) -> Result<Self, Error> {
+        let mut values: HashMap<&'static str, String> = HashMap::new();
+        let mut unreadable: HashSet<&'static str> = HashSet::new();
+        let mut errors: Vec<String> = Vec::new();
+
+        let config_file = match overrides.get(CONFIG_FILE_VAR_HANDLE) {
+            Some(config_file) => Some(config_file.clone()),
+            None => get_env_var(CONFIG_FILE_VAR_HANDLE)?,
+        };
+
+        if let Some(config_file) = config_file {
+            println!("Reading config file {config_file}");
+
+            for (key, value) in read_config_file(Path::new(&config_file))? {
+                match SETTINGS
+                    .iter()
+                    .find(|handle| handle.eq_ignore_ascii_case(&key))
+                {
+                    Some(handle) => {
+                        values.insert(handle, toml_value_to_string(value));
+                    }
+                    None => errors.push(format!("Unknown setting `{key}` in config file")),
+                }
+            }
+        }
+
+        for handle in SETTINGS {
+            match get_env_var(handle) {
+                Ok(Some(value)) => {
+                    values.insert(handle, value);
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    unreadable.insert(handle);
+                    errors.push(format!("{error:#}"));
+                }
+            }
+        }
+
+        values.extend(overrides);
+
+        Ok(Self {
+            values,
+            unreadable,
+            errors,
+        })
+    }
+
+    fn get(&mut self, handle: &str) -> String {
+        self.get_optional(handle).unwrap_or_else(|| {
+            if !self.unreadable.contains(handle) {
+                self.errors
+                    .push(format!("Missing setting {}", describe(handle)));
+            }
+            String::new()
+        })
+    }
+
+    fn get_optional(&self, handle: &str) -> Option<String> {
+        self.values.get(handle).cloned()
+    }
+
+    fn get_bool(&mut self, handle: &str) -> bool {
+        match self.get_optional(handle).as_deref() {
+            None | Some("") | Some("0") | Some("false") => false,
+            Some("1") | Some("true") => true,
+            Some(other) => {
+                self.errors.push(format!(
+                    "Invalid value {other} for {}, expected true or false",
+                    describe(handle)
+                ));
+                false
+            }
+        }
+    }
+
+    /// Splits a comma-separated setting into its trimmed, non-empty items.
+    fn get_list(&self, handle: &str) -> Vec<String> {
+        self.get_optional(handle)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Reads a whole number of at least `min`, or `default` when the setting isn't given.
+    fn get_number<T>(&mut self, handle: &str, default: T, min: T) -> T
+    where
+        T: FromStr + PartialOrd + Display + Copy,
+    {
+        match self.get_optional(handle) {
+            None => default,
+            Some(value) => match value.trim().parse() {
+                Ok(number) if number >= min => number,
+                _ => {
+                    self.errors.push(format!(
+                        "Invalid value {value} for {}, expected a whole number of at least {min}",
+                        describe(handle)
+                    ));
+                    default
+                }
+            },
+        }
+    }
+
+    fn get_property_selection(&self, handle: &str) -> PropertySelection {
+        let keys = self.get_list(handle);
+
+        if keys.is_empty() {
+            PropertySelection::None
+        } else if keys.iter().any(|key| key == "*") {
+            PropertySelection::All
+        } else {
+            PropertySelection::Only(keys)
+        }
+    }
+
+    /// Fails with every missing or invalid setting found while loading.
+    fn finish(self) -> Result<(), Error> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+
+        Err(anyhow!(
+            "Invalid configuration:\n  {}",
+            self.errors.join("\n  ")
+        ))
+    }
+}
+
+fn get_db_target(vars: &mut Vars) -> DbTarget {
+    let version = vars
+        .get_optional(DB_VERSION_VAR_HANDLE)
+        .unwrap_or_else(|| "1".to_string());
+
+    match version.as_str() {
+        "1" => DbTarget::V1 {
+            db_name: vars.get(DB_NAME_VAR_HANDLE),
+            auth: get_v1_auth(vars),
+        },
+        "2" => DbTarget::V2 {
+            org: vars.get(DB_ORG_VAR_HANDLE),
+            bucket: vars.get(DB_BUCKET_VAR_HANDLE),
+            token: vars.get(DB_TOKEN_VAR_HANDLE),
+        },
+        other => {
+            vars.errors.push(format!(
+                "Unsupported value {other} for {}, expected 1 or 2",
+                describe(DB_VERSION_VAR_HANDLE)
+            ));
+            DbTarget::V1 {
+                db_name: String::new(),
+                auth: V1Auth::None,
+            }
+        }
+    }
+}
+
+fn get_v1_auth(vars: &mut Vars) -> V1Auth {
+    let username = vars.get_optional(DB_USERNAME_VAR_HANDLE);
+    let token = vars.get_optional(DB_TOKEN_VAR_HANDLE);
+
+    match (username, token) {
+        (None, None) => V1Auth::None,
+        (Some(username), None) => V1Auth::Basic {
+            username,
+            password: vars.get(DB_PASSWORD_VAR_HANDLE),
+        },
+        (None, Some(token)) => V1Auth::Token(token),
+        (Some(_), Some(_)) => {
+            vars.errors.push(format!(
+                "Both {} and {} are set, expected only one of them",
+                describe(DB_USERNAME_VAR_HANDLE),
+                describe(DB_TOKEN_VAR_HANDLE)
+            ));
+            V1Auth::None
+        }
+    }
+}
+
+/// Returns the settings giving the folder and date format of notes of the period.
+fn period_handles(period: Period) -> (&'static str, &'static str) {
+    match period {
+        Period::Daily => (NOTES_DIR_VAR_HANDLE, DATE_FORMAT_VAR_HANDLE),
+        Period::Weekly => (WEEKLY_NOTES_DIR_VAR_HANDLE, WEEKLY_DATE_FORMAT_VAR_HANDLE),
+        Period::Monthly => (MONTHLY_NOTES_DIR_VAR_HANDLE, MONTHLY_DATE_FORMAT_VAR_HANDLE),
+        Period::Quarterly => (
+            QUARTERLY_NOTES_DIR_VAR_HANDLE,
+            QUARTERLY_DATE_FORMAT_VAR_HANDLE,
+        ),
+        Period::Yearly => (YEARLY_NOTES_DIR_VAR_HANDLE, YEARLY_DATE_FORMAT_VAR_HANDLE),
+    }
+}
+
+fn get_timezone(vars: &mut Vars) -> Tz {
+    match vars.get_optional(TIMEZONE_VAR_HANDLE) {
+        None => Tz::UTC,
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            vars.errors.push(format!(
+                "Invalid value {value} for {}, expected an IANA timezone like Europe/Berlin",
+                describe(TIMEZONE_VAR_HANDLE)
+            ));
+            Tz::UTC
+        }),
+    }
+}
+
+fn get_timestamp_mode(vars: &mut Vars) -> TimestampMode {
+    match vars.get_optional(TIMESTAMP_MODE_VAR_HANDLE) {
+        None => TimestampMode::NoteTime,
+        Some(value) => TimestampMode::parse(&value).unwrap_or_else(|| {
+            vars.errors.push(format!(
+                "Invalid value {value} for {}, expected midnight, note_time or hash",
+                describe(TIMESTAMP_MODE_VAR_HANDLE)
+            ));
+            TimestampMode::NoteTime
+        }),
+    }
+}
+
+fn get_date_match(vars: &mut Vars) -> DateMatch {
+    match vars.get_optional(DATE_MATCH_VAR_HANDLE) {
+        None => DateMatch::Exact,
+        Some(value) => DateMatch::parse(&value).unwrap_or_else(|| {
+            vars.errors.push(format!(
+                "Invalid value {value} for {}, expected exact, prefix or suffix",
+                describe(DATE_MATCH_VAR_HANDLE)
+            ));
+            DateMatch::Exact
+        }),
+    }
+}
+
+/// Works out where the notes of the period are and how they are named. Settings that aren't
+/// given are read from the vault's Obsidian config, or else Obsidian's defaults are used.
+/// Daily notes are always read, while other periods are only read when one of their settings
+/// is given or the vault has them enabled.
+fn get_periodic_notes(
+    vars: &mut Vars,
+    vault_path: &str,
+    period: Period,
+    date_match: DateMatch,
+) -> Option<PeriodicNotes> {
+    let (dir_handle, format_handle) = period_handles(period);
+    let notes_dir = vars.get_optional(dir_handle);
+    let format = vars.get_optional(format_handle);
+
+    let vault_settings = if vault_path.is_empty() {
+        None
+    } else {
+        read_note_settings(Path::new(vault_path), period).unwrap_or_else(|error| {
+            vars.errors.push(format!("{error:#}"));
+            None
+        })
+    };
+
+    if period != Period::Daily
+        && notes_dir.is_none()
+        && format.is_none()
+        && vault_settings.is_none()
+    {
+        return None;
+    }
+
+    let vault_settings: NoteSettings = vault_settings.unwrap_or_default();
+
+    let format = format
+        .or(vault_settings.format)
+        .unwrap_or_else(|| period.default_format().to_string());
+
+    let date_format = DateFormat::parse(&format, date_match).unwrap_or_else(|error| {
+        vars.errors.push(format!(
+            "Invalid value {format} for {}: {error}",
+            describe(format_handle)
+        ));
+        DateFormat::parse(period.default_format(), date_match).unwrap()
+    });
+
+    Some(PeriodicNotes {
+        period,
+        notes_dir: notes_dir.or(vault_settings.folder).unwrap_or_default(),
+        date_format,
+    })
+}
+
+impl Config {
+    /// Builds the configuration from the config file and env vars, with `overrides` (keyed by
+    /// env var handle) taking precedence.
+    pub fn load(overrides: HashMap<&'static str, String>) -> Result<Self, Error> {
+        let mut vars = Vars::load(overrides)?;
+        let db_target = get_db_target(&mut vars);
+
+        // The measurement used to be the database name, so keep that as the default on v1
+        // and mirror it with the bucket name on v2.
+        let measurement = vars
+            .get_optional(DB_MEASUREMENT_VAR_HANDLE)
+            .unwrap_or_else(|| match &db_target {
+                DbTarget::V1 { db_name, .. } => db_name.clone(),
+                DbTarget::V2 { bucket, .. } => bucket.clone(),
+            });
+
+        let vault_path = vars.get(VAULT_PATH_VAR_HANDLE);
+        let date_match = get_date_match(&mut vars);
+
+        let periodic_notes = Period::ALL
+            .into_iter()
+            .filter_map(|period| get_periodic_notes(&mut vars, &vault_path, period, date_match))
+            .collect();
+
+        let config = Self {
+            db_host: vars.get(DB_HOST_VAR_HANDLE),
+            db_port: vars.get(DB_PORT_VAR_HANDLE),
+            db_target,
+            db_tls: vars.get_bool(DB_TLS_VAR_HANDLE),
+            db_ca_cert: vars.get_optional(DB_CA_CERT_VAR_HANDLE).map(PathBuf::from),
+            db_tls_skip_verify: vars.get_bool(DB_TLS_SKIP_VERIFY_VAR_HANDLE),
+            measurement,
+            state_file: vars.get_optional(STATE_FILE_VAR_HANDLE).map(PathBuf::from),
+            batch_size: vars.get_number(BATCH_SIZE_VAR_HANDLE, DEFAULT_BATCH_SIZE, 1),
+            write_retries: vars.get_number(WRITE_RETRIES_VAR_HANDLE, DEFAULT_WRITE_RETRIES, 0),
+            reconcile: vars.get_bool(RECONCILE_VAR_HANDLE),
+            body_tags: vars.get_bool(BODY_TAGS_VAR_HANDLE),
+            tag_rollups: vars.get_bool(TAG_ROLLUPS_VAR_HANDLE),
+            numeric_properties: vars.get_property_selection(NUMERIC_PROPERTIES_VAR_HANDLE),
+            boolean_properties: vars.get_property_selection(BOOLEAN_PROPERTIES_VAR_HANDLE),
+            inline_fields: vars.get_bool(INLINE_FIELDS_VAR_HANDLE),
+            tasks: vars.get_bool(TASKS_VAR_HANDLE),
+            task_tags: vars.get_bool(TASK_TAGS_VAR_HANDLE),
+            writing_stats: vars.get_bool(WRITING_STATS_VAR_HANDLE),
+            date_properties: vars.get_list(DATE_PROPERTY_VAR_HANDLE),
+            timezone: get_timezone(&mut vars),
+            timestamp_mode: get_timestamp_mode(&mut vars),
+            periodic_notes,
+            vault_path,
+        };
+
+        vars.finish()?;
+
+        Ok(config)
+    }
+
+    pub fn db_url(&self) -> String {
+        let scheme = if self.db_tls { "https" } else { "http" };
+
+        format!("{scheme}://{}:{}", self.db_host, self.db_port)
+    }
+}
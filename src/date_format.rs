@@ -0,0 +1,465 @@
+use anyhow::{anyhow, bail, Error};
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use regex::Regex;
+
+const MONTHS: &str =
+    "January|February|March|April|May|June|July|August|September|October|November|December";
+const MONTH_ABBREVIATIONS: &str = "Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec";
+const WEEKDAYS: &str = "Monday|Tuesday|Wednesday|Thursday|Friday|Saturday|Sunday";
+const WEEKDAY_ABBREVIATIONS: &str = "Mon|Tue|Wed|Thu|Fri|Sat|Sun";
+
+/// Moment.js tokens that Obsidian formats are made of, longest first so that `YYYY` isn't read
+/// as two `YY`.
+const MOMENT_TOKENS: &[&str] = &[
+    "YYYY", "YY", "GGGG", "gggg", "Q", "MMMM", "MMM", "MM", "M", "WW", "W", "ww", "w", "DDDD",
+    "DDD", "DD", "Do", "D", "dddd", "ddd", "dd", "d",
+];
+
+/// How much of a note's file name the date format has to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateMatch {
+    /// The whole file name is the date.
+    Exact,
+    /// The file name starts with the date, like `2024-03-05 Tuesday`.
+    Prefix,
+    /// The file name ends with the date, like `Journal 2024-03-05`.
+    Suffix,
+}
+
+impl DateMatch {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "exact" => Some(Self::Exact),
+            "prefix" => Some(Self::Prefix),
+            "suffix" => Some(Self::Suffix),
+            _ => None,
+        }
+    }
+}
+
+/// Part of a date that a token of the format captures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Component {
+    Year,
+    ShortYear,
+    /// Year of an ISO week, which can differ from the calendar year around new year.
+    IsoWeekYear,
+    IsoWeek,
+    /// Year of a week in the US locale Obsidian uses by default, whose weeks start on Sunday.
+    LocaleWeekYear,
+    LocaleWeek,
+    Quarter,
+    Month,
+    MonthName,
+    Day,
+    DayOfYear,
+}
+
+/// A date format for note names, either in Moment.js tokens as Obsidian uses them
+/// (`YYYY-MM-DD`) or in strftime specifiers (`%Y-%m-%d`). Formats with a `/`, like
+/// `YYYY/MM/YYYY-MM-DD`, are matched against the note's path within the notes directory rather
+/// than its file name.
+#[derive(Debug, Clone)]
+pub struct DateFormat {
+    format: String,
+    regex: Regex,
+    /// What each capture group of `regex` holds, in order.
+    components: Vec<Component>,
+}
+
+/// Returns the regex and captured component of a Moment.js token.
+fn moment_token(token: &str) -> (String, Option<Component>) {
+    let (pattern, component) = match token {
+        "YYYY" => (r"(\d{4})".to_string(), Some(Component::Year)),
+        "YY" => (r"(\d{2})".to_string(), Some(Component::ShortYear)),
+        "GGGG" => (r"(\d{4})".to_string(), Some(Component::IsoWeekYear)),
+        "gggg" => (r"(\d{4})".to_string(), Some(Component::LocaleWeekYear)),
+        "Q" => ("([1-4])".to_string(), Some(Component::Quarter)),
+        "MMMM" => (format!("(?i:({MONTHS}))"), Some(Component::MonthName)),
+        "MMM" => (
+            format!("(?i:({MONTH_ABBREVIATIONS}))"),
+            Some(Component::MonthName),
+        ),
+        "MM" => (r"(\d{2})".to_string(), Some(Component::Month)),
+        "M" => (r"(\d{1,2})".to_string(), Some(Component::Month)),
+        "WW" => (r"(\d{2})".to_string(), Some(Component::IsoWeek)),
+        "W" => (r"(\d{1,2})".to_string(), Some(Component::IsoWeek)),
+        "ww" => (r"(\d{2})".to_string(), Some(Component::LocaleWeek)),
+        "w" => (r"(\d{1,2})".to_string(), Some(Component::LocaleWeek)),
+        "DDDD" => (r"(\d{3})".to_string(), Some(Component::DayOfYear)),
+        "DDD" => (r"(\d{1,3})".to_string(), Some(Component::DayOfYear)),
+        "DD" => (r"(\d{2})".to_string(), Some(Component::Day)),
+        "Do" => (
+            r"(\d{1,2})(?:st|nd|rd|th)".to_string(),
+            Some(Component::Day),
+        ),
+        "D" => (r"(\d{1,2})".to_string(), Some(Component::Day)),
+        "dddd" => (format!("(?i:{WEEKDAYS})"), None),
+        "ddd" => (format!("(?i:{WEEKDAY_ABBREVIATIONS})"), None),
+        "dd" => ("(?i:Mo|Tu|We|Th|Fr|Sa|Su)".to_string(), None),
+        _ => ("[0-6]".to_string(), None),
+    };
+
+    (pattern, component)
+}
+
+/// Returns the regex and captured component of a strftime specifier, without its `%`.
+fn strftime_specifier(specifier: &str) -> Result<(String, Option<Component>), Error> {
+    let token = match specifier {
+        "Y" => "YYYY",
+        "y" => "YY",
+        "G" => "GGGG",
+        "V" => "WW",
+        "B" => "MMMM",
+        "b" | "h" => "MMM",
+        "m" => "MM",
+        "-m" => "M",
+        "j" => "DDDD",
+        "d" => "DD",
+        "-d" => "D",
+        "e" => return Ok((r"\s?(\d{1,2})".to_string(), Some(Component::Day))),
+        "A" => "dddd",
+        "a" => "ddd",
+        "%" => return Ok(("%".to_string(), None)),
+        other => bail!("unsupported specifier %{other}"),
+    };
+
+    Ok(moment_token(token))
+}
+
+/// Sets a component of a date, failing if it was already set to something else.
+fn set<T: PartialEq>(slot: &mut Option<T>, value: T) -> Option<()> {
+    match slot {
+        Some(existing) if *existing != value => None,
+        _ => {
+            *slot = Some(value);
+            Some(())
+        }
+    }
+}
+
+impl DateFormat {
+    pub fn parse(format: &str, matching: DateMatch) -> Result<Self, Error> {
+        let mut pattern = String::new();
+        let mut components: Vec<Component> = Vec::new();
+
+        let mut push = |(fragment, component): (String, Option<Component>)| {
+            pattern.push_str(&fragment);
+            components.extend(component);
+        };
+
+        if format.contains('%') {
+            let expanded = format.replace("%F", "%Y-%m-%d");
+            let mut rest = expanded.as_str();
+
+            while let Some(index) = rest.find('%') {
+                push((regex::escape(&rest[..index]), None));
+
+                let specifier_len = if rest[index + 1..].starts_with('-') {
+                    2
+                } else {
+                    1
+                };
+                let specifier = rest
+                    .get(index + 1..index + 1 + specifier_len)
+                    .ok_or_else(|| anyhow!("incomplete specifier at the end"))?;
+
+                push(strftime_specifier(specifier)?);
+                rest = &rest[index + 1 + specifier_len..];
+            }
+
+            push((regex::escape(rest), None));
+        } else {
+            let mut rest = format;
+
+            while let Some(c) = rest.chars().next() {
+                if c == '[' {
+                    // Text in brackets is escaped, like `[W]` in `gggg-[W]ww`.
+                    let end = rest.find(']').ok_or_else(|| anyhow!("unclosed ["))?;
+
+                    push((regex::escape(&rest[1..end]), None));
+                    rest = &rest[end + 1..];
+                } else if let Some(token) = MOMENT_TOKENS.iter().find(|t| rest.starts_with(*t)) {
+                    push(moment_token(token));
+                    rest = &rest[token.len()..];
+                } else {
+                    push((regex::escape(&c.to_string()), None));
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+
+        if !components.iter().any(|c| {
+            matches!(
+                c,
+                Component::Year
+                    | Component::ShortYear
+                    | Component::IsoWeekYear
+                    | Component::LocaleWeekYear
+            )
+        }) {
+            bail!("the format has no year");
+        }
+
+        let pattern = match matching {
+            DateMatch::Exact => format!("^{pattern}$"),
+            DateMatch::Prefix => format!("^{pattern}"),
+            DateMatch::Suffix => format!("{pattern}$"),
+        };
+
+        Ok(Self {
+            format: format.to_string(),
+            regex: Regex::new(&pattern)?,
+            components,
+        })
+    }
+
+    /// Whether the format spans folders, so that it is matched against the path of notes.
+    pub fn spans_folders(&self) -> bool {
+        self.format.contains('/')
+    }
+
+    /// Reads the date from a note's name, without its extension. Names without a day give the
+    /// first day of their period: Monday for ISO weeks, Sunday for locale weeks, and the first of
+    /// the month, quarter or year. A component that appears twice, like the year in
+    /// `YYYY/YYYY-MM-DD`, has to be the same both times.
+    pub fn date_of(&self, name: &str) -> Option<NaiveDate> {
+        let captures = self.regex.captures(name)?;
+
+        let mut year: Option<i32> = None;
+        let mut iso_week_year: Option<i32> = None;
+        let mut iso_week: Option<u32> = None;
+        let mut locale_week_year: Option<i32> = None;
+        let mut locale_week: Option<u32> = None;
+        let mut quarter: Option<u32> = None;
+        let mut month: Option<u32> = None;
+        let mut day: Option<u32> = None;
+        let mut day_of_year: Option<u32> = None;
+
+        for (component, capture) in self.components.iter().zip(captures.iter().skip(1)) {
+            let text = capture?.as_str();
+
+            match component {
+                Component::Year => set(&mut year, text.parse().ok()?)?,
+                Component::ShortYear => set(&mut year, 2000 + text.parse::<i32>().ok()?)?,
+                Component::IsoWeekYear => set(&mut iso_week_year, text.parse().ok()?)?,
+                Component::IsoWeek => set(&mut iso_week, text.parse().ok()?)?,
+                Component::LocaleWeekYear => set(&mut locale_week_year, text.parse().ok()?)?,
+                Component::LocaleWeek => set(&mut locale_week, text.parse().ok()?)?,
+                Component::Quarter => set(&mut quarter, text.parse().ok()?)?,
+                Component::Month => set(&mut month, text.parse().ok()?)?,
+                Component::MonthName => {
+                    let index = MONTH_ABBREVIATIONS.split('|').position(|abbreviation| {
+                        text.get(..3)
+                            .is_some_and(|t| t.eq_ignore_ascii_case(abbreviation))
+                    })?;
+
+                    set(&mut month, u32::try_from(index).ok()? + 1)?
+                }
+                Component::Day => set(&mut day, text.parse().ok()?)?,
+                Component::DayOfYear => set(&mut day_of_year, text.parse().ok()?)?,
+            }
+        }
+
+        if let Some(week) = iso_week {
+            return NaiveDate::from_isoywd_opt(iso_week_year.or(year)?, week, Weekday::Mon);
+        }
+
+        if let Some(week) = locale_week {
+            // Week 1 is the one with January 1st in it.
+            let new_year = NaiveDate::from_ymd_opt(locale_week_year.or(year)?, 1, 1)?;
+            let first_sunday =
+                new_year - Days::new(u64::from(new_year.weekday().num_days_from_sunday()));
+
+            return first_sunday.checked_add_days(Days::new(7 * u64::from(week.checked_sub(1)?)));
+        }
+
+        let year = year.or(iso_week_year).or(locale_week_year)?;
+        let month = month.or(quarter.map(|quarter| quarter * 3 - 2));
+
+        match day_of_year {
+            Some(ordinal) => NaiveDate::from_yo_opt(year, ordinal),
+            None => NaiveDate::from_ymd_opt(year, month.unwrap_or(1), day.unwrap_or(1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date_of(format: &str, matching: DateMatch, name: &str) -> Option<NaiveDate> {
+        DateFormat::parse(format, matching).unwrap().date_of(name)
+    }
+
+    fn ymd(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    #[test]
+    fn reads_days() {
+        let exact = DateMatch::Exact;
+
+        assert_eq!(date_of("YYYY-MM-DD", exact, "2024-03-05"), ymd(2024, 3, 5));
+        assert_eq!(date_of("DD.MM.YYYY", exact, "05.03.2024"), ymd(2024, 3, 5));
+        assert_eq!(date_of("YYYYMMDD", exact, "20240305"), ymd(2024, 3, 5));
+        assert_eq!(date_of("YY-M-D", exact, "24-3-5"), ymd(2024, 3, 5));
+        assert_eq!(date_of("YYYY-DDDD", exact, "2024-065"), ymd(2024, 3, 5));
+    }
+
+    #[test]
+    fn reads_names_of_months_and_weekdays() {
+        let exact = DateMatch::Exact;
+
+        assert_eq!(
+            date_of("MMMM Do, YYYY", exact, "March 5th, 2024"),
+            ymd(2024, 3, 5)
+        );
+        assert_eq!(date_of("D MMM YYYY", exact, "5 mar 2024"), ymd(2024, 3, 5));
+        assert_eq!(
+            date_of("dddd, MMMM D, YYYY", exact, "Tuesday, March 5, 2024"),
+            ymd(2024, 3, 5)
+        );
+        assert_eq!(
+            date_of("YYYY-MM-DD ddd", exact, "2024-03-05 Tue"),
+            ymd(2024, 3, 5)
+        );
+    }
+
+    #[test]
+    fn reads_strftime_formats() {
+        let exact = DateMatch::Exact;
+
+        assert_eq!(date_of("%Y-%m-%d", exact, "2024-03-05"), ymd(2024, 3, 5));
+        assert_eq!(date_of("%F", exact, "2024-03-05"), ymd(2024, 3, 5));
+        assert_eq!(date_of("%d %B %Y", exact, "05 March 2024"), ymd(2024, 3, 5));
+        assert_eq!(date_of("%-d.%-m.%y", exact, "5.3.24"), ymd(2024, 3, 5));
+        assert_eq!(date_of("%Y-%j", exact, "2024-065"), ymd(2024, 3, 5));
+        assert_eq!(date_of("%Y %%d", exact, "2024 %d"), ymd(2024, 1, 1));
+    }
+
+    #[test]
+    fn keeps_bracketed_text_literal() {
+        let exact = DateMatch::Exact;
+
+        assert_eq!(
+            date_of("[Daily] YYYY-MM-DD", exact, "Daily 2024-03-05"),
+            ymd(2024, 3, 5)
+        );
+        assert_eq!(
+            date_of("[Daily] YYYY-MM-DD", exact, "Dai5y 2024-03-05"),
+            None
+        );
+        assert_eq!(date_of("YYYY.MM.DD", exact, "2024x03x05"), None);
+    }
+
+    #[test]
+    fn reads_the_start_of_longer_periods() {
+        let exact = DateMatch::Exact;
+
+        assert_eq!(date_of("YYYY-MM", exact, "2024-03"), ymd(2024, 3, 1));
+        assert_eq!(date_of("YYYY-[Q]Q", exact, "2024-Q2"), ymd(2024, 4, 1));
+        assert_eq!(date_of("YYYY", exact, "2024"), ymd(2024, 1, 1));
+    }
+
+    #[test]
+    fn reads_iso_weeks_from_monday() {
+        let exact = DateMatch::Exact;
+
+        assert_eq!(date_of("GGGG-[W]WW", exact, "2024-W10"), ymd(2024, 3, 4));
+        // The first ISO week of 2021 starts in January, as 2020 has a week 53.
+        assert_eq!(date_of("GGGG-[W]WW", exact, "2020-W53"), ymd(2020, 12, 28));
+        assert_eq!(date_of("GGGG-[W]W", exact, "2021-W1"), ymd(2021, 1, 4));
+        assert_eq!(date_of("GGGG-[W]WW", exact, "2021-W54"), None);
+    }
+
+    #[test]
+    fn reads_locale_weeks_from_sunday() {
+        let exact = DateMatch::Exact;
+
+        assert_eq!(date_of("gggg-[W]ww", exact, "2024-W10"), ymd(2024, 3, 3));
+        // Week 1 is the one with January 1st in it, which can start in December.
+        assert_eq!(date_of("gggg-[W]ww", exact, "2021-W01"), ymd(2020, 12, 27));
+        assert_eq!(date_of("gggg-[W]w", exact, "2023-W1"), ymd(2023, 1, 1));
+        assert_eq!(date_of("gggg-[W]ww", exact, "2024-W00"), None);
+    }
+
+    #[test]
+    fn matches_formats_across_folders() {
+        let exact = DateMatch::Exact;
+        let format = DateFormat::parse("YYYY/MM/YYYY-MM-DD", exact).unwrap();
+
+        assert!(format.spans_folders());
+        assert!(!DateFormat::parse("YYYY-MM-DD", exact)
+            .unwrap()
+            .spans_folders());
+        assert_eq!(format.date_of("2024/03/2024-03-05"), ymd(2024, 3, 5));
+    }
+
+    #[test]
+    fn requires_repeated_components_to_agree() {
+        let exact = DateMatch::Exact;
+
+        assert_eq!(
+            date_of("YYYY/MM/YYYY-MM-DD", exact, "2023/03/2024-03-05"),
+            None
+        );
+        assert_eq!(
+            date_of("YYYY/MM/YYYY-MM-DD", exact, "2024/04/2024-03-05"),
+            None
+        );
+        assert_eq!(date_of("YYYY-MM-DD MMMM", exact, "2024-03-05 April"), None);
+    }
+
+    #[test]
+    fn matches_prefixes_and_suffixes() {
+        assert_eq!(
+            date_of("YYYY-MM-DD", DateMatch::Prefix, "2024-03-05 Tuesday"),
+            ymd(2024, 3, 5)
+        );
+        assert_eq!(
+            date_of("YYYY-MM-DD", DateMatch::Exact, "2024-03-05 Tuesday"),
+            None
+        );
+        assert_eq!(
+            date_of("YYYY-MM-DD", DateMatch::Prefix, "Journal 2024-03-05"),
+            None
+        );
+        assert_eq!(
+            date_of("YYYY-MM-DD", DateMatch::Suffix, "Journal 2024-03-05"),
+            ymd(2024, 3, 5)
+        );
+        assert_eq!(
+            date_of("YYYY-MM-DD", DateMatch::Suffix, "2024-03-05 Tuesday"),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        let exact = DateMatch::Exact;
+
+        assert_eq!(date_of("YYYY-MM-DD", exact, "2024-02-30"), None);
+        assert_eq!(date_of("YYYY-MM-DD", exact, "2024-13-01"), None);
+        assert_eq!(date_of("YYYY-MM-DD", exact, "2024-3-5"), None);
+        assert_eq!(date_of("YYYY-MM-DD", exact, "notes"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_formats() {
+        let exact = DateMatch::Exact;
+
+        assert!(DateFormat::parse("MM-DD", exact).is_err());
+        assert!(DateFormat::parse("[Daily YYYY-MM-DD", exact).is_err());
+        assert!(DateFormat::parse("%Y-%m-%", exact).is_err());
+        assert!(DateFormat::parse("%Y-%m-%Q", exact).is_err());
+    }
+
+    #[test]
+    fn parses_date_matches() {
+        assert_eq!(DateMatch::parse("exact"), Some(DateMatch::Exact));
+        assert_eq!(DateMatch::parse("prefix"), Some(DateMatch::Prefix));
+        assert_eq!(DateMatch::parse("suffix"), Some(DateMatch::Suffix));
+        assert_eq!(DateMatch::parse("start"), None);
+    }
+}
@@ -0,0 +1,626 @@
+use std::{fmt, fs, time::Duration};
+
+use anyhow::{Context, Error};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use influxdb::{integrations::serde_integration::DatabaseQueryResult, Query, WriteQuery};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::{
+    config::{Config, DbTarget, V1Auth},
+    period::Period,
+    timezone::day_range,
+};
+
+/// Requests taking longer than this are abandoned, so that a stalled write can be retried.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Delay before the first retry of a failed write, doubled for every retry after it.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Only the timestamp is needed to find where the previous run stopped.
+#[derive(Debug, Deserialize)]
+struct LatestEntry {
+    time: DateTime<Utc>,
+}
+
+/// A tag stored for a point, used to compare what is in the database with the notes.
+#[derive(Debug, Deserialize)]
+pub struct StoredTag {
+    pub time: DateTime<Utc>,
+    pub frontmatter_tag: String,
+    /// Where the tag was found, unset on rollup points and on points written before it was
+    /// recorded.
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Period of the note, unset on points written before it was recorded.
+    #[serde(default)]
+    pub period: Option<String>,
+}
+
+/// Minimal client for the InfluxDB 1.x HTTP API. Requests are sent directly rather than through
+/// the `influxdb` crate, as it puts credentials in the query string and doesn't report the
+/// status of a failed write.
+pub struct V1Client {
+    url: String,
+    db_name: String,
+    auth: V1Auth,
+    http: reqwest::Client,
+}
+
+/// Minimal client for the InfluxDB 2.x HTTP API, which the `influxdb` crate only
+/// supports through the 1.x compatibility endpoints.
+pub struct V2Client {
+    url: String,
+    org: String,
+    bucket: String,
+    token: String,
+    http: reqwest::Client,
+}
+
+/// A request to the InfluxDB API that was answered with an error status.
+#[derive(Debug)]
+struct StatusError {
+    request: &'static str,
+    status: StatusCode,
+    body: String,
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failed with status {}: {}",
+            self.request, self.status, self.body
+        )
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+/// Whether a failed request may succeed when sent again: the server had an error of its own,
+/// or it couldn't be reached or didn't answer in time.
+fn is_transient(error: &Error) -> bool {
+    if let Some(error) = error.downcast_ref::<StatusError>() {
+        return error.status.is_server_error();
+    }
+
+    error
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(|error| error.is_timeout() || error.is_connect())
+}
+
+pub enum Database {
+    V1(V1Client),
+    V2(V2Client),
+}
+
+impl V1Client {
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.auth {
+            V1Auth::None => request,
+            V1Auth::Basic { username, password } => request.basic_auth(username, Some(password)),
+            V1Auth::Token(token) => request.header("Authorization", format!("Token {token}")),
+        }
+    }
+
+    /// Runs an InfluxQL statement, failing if the server or the statement reports an error.
+    async fn query(&self, statement: String) -> Result<DatabaseQueryResult, Error> {
+        let request = self
+            .http
+            .post(format!("{}/query", self.url))
+            .query(&[("db", self.db_name.as_str())])
+            .form(&[("q", statement.as_str())]);
+
+        let response = self.authorize(request).send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            return Err(StatusError {
+                request: "InfluxQL query",
+                status,
+                body,
+            }
+            .into());
+        }
+
+        let db_result: DatabaseQueryResult =
+            serde_json::from_str(&body).context("Could not parse query result")?;
+
+        if let Some(error) = db_result
+            .results
+            .iter()
+            .find_map(|result| result.get("error")?.as_str())
+        {
+            anyhow::bail!("Query failed: {error}");
+        }
+
+        Ok(db_result)
+    }
+
+    async fn write_lines(&self, lines: String, precision: &str) -> Result<(), Error> {
+        let request = self
+            .http
+            .post(format!("{}/write", self.url))
+            .query(&[("db", self.db_name.as_str()), ("precision", precision)])
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(lines);
+
+        let response = self.authorize(request).send().await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(StatusError {
+                request: "Write",
+                status,
+                body,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+impl V2Client {
+    async fn query_csv(&self, flux: String) -> Result<String, Error> {
+        let response = self
+            .http
+            .post(format!("{}/api/v2/query", self.url))
+            .query(&[("org", &self.org)])
+            .header("Authorization", format!("Token {}", self.token))
+            .header("Accept", "application/csv")
+            .header("Content-Type", "application/vnd.flux")
+            .body(flux)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            return Err(StatusError {
+                request: "Flux query",
+                status,
+                body,
+            }
+            .into());
+        }
+
+        Ok(body)
+    }
+
+    async fn delete(
+        &self,
+        start: DateTime<Utc>,
+        stop: DateTime<Utc>,
+        predicate: String,
+    ) -> Result<(), Error> {
+        let body = serde_json::json!({
+            "start": start.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            "stop": stop.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            "predicate": predicate,
+        });
+
+        let response = self
+            .http
+            .post(format!("{}/api/v2/delete", self.url))
+            .query(&[("org", self.org.as_str()), ("bucket", self.bucket.as_str())])
+            .header("Authorization", format!("Token {}", self.token))
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await?;
+
+        let status = response.status();
+
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(StatusError {
+                request: "Delete",
+                status,
+                body,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    async fn write_lines(&self, lines: String, precision: &str) -> Result<(), Error> {
+        let response = self
+            .http
+            .post(format!("{}/api/v2/write", self.url))
+            .query(&[
+                ("org", self.org.as_str()),
+                ("bucket", self.bucket.as_str()),
+                ("precision", precision),
+            ])
+            .header("Authorization", format!("Token {}", self.token))
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(lines)
+            .send()
+            .await?;
+
+        let status = response.status();
+
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(StatusError {
+                request: "Write",
+                status,
+                body,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits a CSV row into its fields, unquoting quoted ones.
+fn split_csv_row(row: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = row.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+
+    fields.push(field);
+
+    fields
+}
+
+/// Reads the values of `columns` from every row of an InfluxDB 2.x CSV query response.
+/// Each table in the response starts with its own header row after a blank line. Columns
+/// missing from a table, like tags none of its series have, are read as empty.
+fn csv_values(csv: &str, columns: &[&str]) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut indices: Option<Vec<Option<usize>>> = None;
+
+    for line in csv.lines().map(str::trim) {
+        if line.is_empty() {
+            indices = None;
+            continue;
+        }
+
+        let fields = split_csv_row(line);
+
+        match &indices {
+            None => {
+                indices = Some(
+                    columns
+                        .iter()
+                        .map(|column| fields.iter().position(|name| name == column))
+                        .collect(),
+                );
+            }
+            Some(indices) => rows.push(
+                indices
+                    .iter()
+                    .map(|index| {
+                        index
+                            .and_then(|index| fields.get(index).cloned())
+                            .unwrap_or_default()
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    rows
+}
+
+fn parse_csv_time(time: &str) -> Result<DateTime<Utc>, Error> {
+    DateTime::parse_from_rfc3339(time)
+        .map(|time| time.with_timezone(&Utc))
+        .context(format!("Could not parse time {time} from query result"))
+}
+
+/// Builds the HTTP client for every request to InfluxDB, trusting the configured certificates.
+fn http_client(config: &Config) -> Result<reqwest::Client, Error> {
+    let mut builder = reqwest::Client::builder().timeout(REQUEST_TIMEOUT);
+
+    if let Some(ca_cert) = &config.db_ca_cert {
+        let pem = fs::read(ca_cert).context(format!(
+            "Could not read CA certificate {:?}",
+            ca_cert.as_os_str()
+        ))?;
+        let certificate = reqwest::Certificate::from_pem(&pem).context(format!(
+            "Could not parse CA certificate {:?}",
+            ca_cert.as_os_str()
+        ))?;
+
+        builder = builder.add_root_certificate(certificate);
+    }
+
+    if config.db_tls_skip_verify {
+        println!("Warning: not verifying the InfluxDB certificate");
+
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    builder.build().context("Could not create HTTP client")
+}
+
+impl Database {
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let http = http_client(config)?;
+
+        Ok(match &config.db_target {
+            DbTarget::V1 { db_name, auth } => Self::V1(V1Client {
+                url: config.db_url(),
+                db_name: db_name.clone(),
+                auth: auth.clone(),
+                http,
+            }),
+            DbTarget::V2 { org, bucket, token } => Self::V2(V2Client {
+                url: config.db_url(),
+                org: org.clone(),
+                bucket: bucket.clone(),
+                token: token.clone(),
+                http,
+            }),
+        })
+    }
+
+    /// Returns the time of the newest point in the measurement, or `None` if it is empty.
+    pub async fn get_latest_date(&self, measurement: &str) -> Result<Option<DateTime<Utc>>, Error> {
+        match self {
+            Self::V1(client) => {
+                let mut db_result = client
+                    .query(format!(
+                        "SELECT * FROM {measurement} ORDER BY time DESC LIMIT 1"
+                    ))
+                    .await?;
+
+                Ok(db_result
+                    .deserialize_next::<LatestEntry>()?
+                    .series
+                    .first()
+                    .and_then(|series| series.values.first())
+                    .map(|entry| entry.time))
+            }
+            Self::V2(client) => {
+                let flux = format!(
+                    "from(bucket: \"{}\") \
+                     |> range(start: 0) \
+                     |> filter(fn: (r) => r._measurement == \"{measurement}\") \
+                     |> keep(columns: [\"_time\"]) \
+                     |> group() \
+                     |> sort(columns: [\"_time\"], desc: true) \
+                     |> limit(n: 1)",
+                    client.bucket
+                );
+
+                let csv = client.query_csv(flux).await?;
+
+                csv_values(&csv, &["_time"])
+                    .first()
+                    .map(|row| parse_csv_time(&row[0]))
+                    .transpose()
+            }
+        }
+    }
+
+    /// Returns the time, `frontmatter_tag`, `source` and `period` of every point of the measurement in `[start, stop)`.
+    pub async fn get_stored_tags(
+        &self,
+        measurement: &str,
+        start: DateTime<Utc>,
+        stop: DateTime<Utc>,
+    ) -> Result<Vec<StoredTag>, Error> {
+        match self {
+            Self::V1(client) => {
+                let mut db_result = client
+                    .query(format!(
+                    "SELECT \"value\", \"frontmatter_tag\", \"source\", \"period\" FROM \"{measurement}\" \
+                     WHERE time >= '{}' AND time < '{}'",
+                    start.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    stop.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    ))
+                    .await?;
+
+                Ok(db_result
+                    .deserialize_next::<StoredTag>()?
+                    .series
+                    .into_iter()
+                    .flat_map(|series| series.values)
+                    .collect())
+            }
+            Self::V2(client) => {
+                let flux = format!(
+                    "from(bucket: \"{}\") \
+                     |> range(start: {}, stop: {}) \
+                     |> filter(fn: (r) => r._measurement == \"{measurement}\" and r._field == \"value\") \
+                     |> keep(columns: [\"_time\", \"frontmatter_tag\", \"source\", \"period\"]) \
+                     |> group()",
+                    client.bucket,
+                    start.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    stop.to_rfc3339_opts(SecondsFormat::Secs, true),
+                );
+
+                let csv = client.query_csv(flux).await?;
+
+                csv_values(&csv, &["_time", "frontmatter_tag", "source", "period"])
+                    .into_iter()
+                    .map(|row| {
+                        Ok(StoredTag {
+                            time: parse_csv_time(&row[0])?,
+                            frontmatter_tag: row[1].clone(),
+                            source: Some(row[2].clone()).filter(|source| !source.is_empty()),
+                            period: Some(row[3].clone()).filter(|period| !period.is_empty()),
+                        })
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Deletes the points of the measurement in `[start, stop)`, only those of the series with
+    /// all of the given tag keys and values when there are any.
+    pub async fn delete_points(
+        &self,
+        measurement: &str,
+        start: DateTime<Utc>,
+        stop: DateTime<Utc>,
+        tags: &[(&str, &str)],
+    ) -> Result<(), Error> {
+        match self {
+            Self::V1(client) => {
+                let mut statement = format!(
+                    "DELETE FROM \"{measurement}\" WHERE time >= '{}' AND time < '{}'",
+                    start.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    stop.to_rfc3339_opts(SecondsFormat::Secs, true),
+                );
+
+                for (key, value) in tags {
+                    statement.push_str(&format!(
+                        " AND \"{key}\" = '{}'",
+                        value.replace('\\', "\\\\").replace('\'', "\\'")
+                    ));
+                }
+
+                client.query(statement).await?;
+            }
+            Self::V2(client) => {
+                // The delete API treats the stop time as inclusive, so step back a nanosecond
+                // to keep the range half-open like on v1.
+                let stop = stop - chrono::Duration::nanoseconds(1);
+                let mut predicate = format!("_measurement=\"{measurement}\"");
+
+                for (key, value) in tags {
+                    predicate.push_str(&format!(
+                        " AND {key}=\"{}\"",
+                        value.replace('\\', "\\\\").replace('"', "\\\"")
+                    ));
+                }
+
+                client.delete(start, stop, predicate).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the points of the measurement on the given day that belong to notes of the period,
+    /// leaving those of notes of other periods starting on the same day.
+    pub async fn delete_day(
+        &self,
+        measurement: &str,
+        date: NaiveDate,
+        period: Period,
+        timezone: Tz,
+    ) -> Result<(), Error> {
+        let (start, stop) = day_range(date, timezone);
+
+        self.delete_points(measurement, start, stop, &[("period", period.as_str())])
+            .await
+    }
+
+    /// Writes the points to the database in a single request, retrying up to `retries` times
+    /// with a growing delay while it fails with a server error or timeout.
+    pub async fn write(&self, inserts: &[WriteQuery], retries: u32) -> Result<(), Error> {
+        let mut delay = RETRY_DELAY;
+
+        for retry in 1..=retries {
+            match self.write_once(inserts).await {
+                Err(error) if is_transient(&error) => {
+                    println!(
+                        "Write failed ({error}), retrying in {}s ({retry}/{retries})...",
+                        delay.as_secs()
+                    );
+
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+
+        self.write_once(inserts).await
+    }
+
+    /// Writes the points to the database. Both versions receive the same line protocol
+    /// (integers are written with the 1.x `i` suffix), so the stored points are identical.
+    async fn write_once(&self, inserts: &[WriteQuery]) -> Result<(), Error> {
+        let precision = inserts
+            .first()
+            .map_or_else(|| "ns".to_string(), WriteQuery::get_precision);
+        let lines = inserts.to_vec().build()?.get();
+
+        match self {
+            Self::V1(client) => client.write_lines(lines, &precision).await,
+            Self::V2(client) => client.write_lines(lines, &precision).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_csv_rows() {
+        assert_eq!(split_csv_row("a,b,,c"), vec!["a", "b", "", "c"]);
+        assert_eq!(
+            split_csv_row(r#",_result,"a,b","say ""hi""""#),
+            vec!["", "_result", "a,b", r#"say "hi""#]
+        );
+        assert_eq!(split_csv_row(""), vec![""]);
+    }
+
+    #[test]
+    fn reads_csv_columns_from_every_table() {
+        let csv = ",result,table,_time,frontmatter_tag\r\n\
+                   ,_result,0,2024-03-05T00:00:00Z,\"a,b\"\r\n\
+                   ,_result,0,2024-03-06T00:00:00Z,gym\r\n\
+                   \r\n\
+                   ,result,table,frontmatter_tag,_time\r\n\
+                   ,_result,1,run,2024-03-07T00:00:00Z\r\n";
+
+        assert_eq!(
+            csv_values(csv, &["_time", "frontmatter_tag"]),
+            vec![
+                vec!["2024-03-05T00:00:00Z", "a,b"],
+                vec!["2024-03-06T00:00:00Z", "gym"],
+                vec!["2024-03-07T00:00:00Z", "run"],
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_missing_csv_columns_as_empty() {
+        let csv = ",result,table,_time,frontmatter_tag\r\n\
+                   ,_result,0,2024-03-05T00:00:00Z,gym\r\n";
+
+        assert_eq!(
+            csv_values(csv, &["frontmatter_tag", "source"]),
+            vec![vec!["gym", ""]]
+        );
+        assert!(csv_values("", &["_time"]).is_empty());
+    }
+
+    #[test]
+    fn parses_csv_times() {
+        assert_eq!(
+            parse_csv_time("2024-03-05T01:02:03.5+01:00").unwrap(),
+            DateTime::parse_from_rfc3339("2024-03-05T00:02:03.5Z").unwrap()
+        );
+        assert!(parse_csv_time("yesterday").is_err());
+    }
+}
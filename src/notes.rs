@@ -0,0 +1,498 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use chrono::{
+    naive::{NaiveDate, NaiveDateTime, NaiveTime},
+    DateTime, Duration, Utc,
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Deserializer};
+use serde_yaml::Value;
+use sha2::{Digest, Sha256};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::{
+    body::{
+        extract_inline_fields, extract_tags, extract_tasks, writing_stats, FieldValue, Task,
+        WritingStats,
+    },
+    config::{Config, PeriodicNotes, TimestampMode},
+    period::Period,
+    timezone::{get_yesterday, to_utc},
+};
+
+const NOTE_FILE_EXTENSION: &str = ".md";
+const DATE_PROPERTY_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(from = "RawFrontmatter")]
+pub struct Frontmatter {
+    /// Tags without their leading `#`, from both `tags` and the legacy `tag` key.
+    pub tags: Vec<String>,
+    /// Every other frontmatter property.
+    pub properties: BTreeMap<String, Value>,
+}
+
+/// Frontmatter as written, where a note can have both `tags` and the legacy `tag` key.
+#[derive(Deserialize)]
+struct RawFrontmatter {
+    #[serde(default, deserialize_with = "deserialize_tags")]
+    tags: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_tags")]
+    tag: Vec<String>,
+    #[serde(flatten)]
+    properties: BTreeMap<String, Value>,
+}
+
+impl From<RawFrontmatter> for Frontmatter {
+    fn from(raw: RawFrontmatter) -> Self {
+        let mut tags = raw.tags;
+
+        for tag in raw.tag {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        Self {
+            tags,
+            properties: raw.properties,
+        }
+    }
+}
+
+/// Normalizes a single tag, which Obsidian accepts with or without a leading `#`.
+fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches('#');
+
+    (!tag.is_empty()).then(|| tag.to_string())
+}
+
+/// Accepts tags as a YAML list or as a string separated by commas or spaces, the forms Obsidian
+/// reads, with or without a leading `#` on each.
+fn deserialize_tags<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let tags = match Value::deserialize(deserializer)? {
+        Value::String(tags) => tags
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter_map(normalize_tag)
+            .collect(),
+        Value::Sequence(tags) => tags
+            .iter()
+            .filter_map(Value::as_str)
+            .filter_map(normalize_tag)
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(tags)
+}
+
+/// Ancestors of a nested tag from the root down, e.g. `health` and `health/sleep` for
+/// `health/sleep/good`.
+pub fn tag_ancestors(tag: &str) -> impl Iterator<Item = &str> {
+    tag.match_indices('/').map(move |(index, _)| &tag[..index])
+}
+
+/// Where a tag of a note was found, written as the `source` Influx tag.
+#[derive(Debug, Clone, Copy)]
+pub enum TagSource {
+    Frontmatter,
+    Body,
+}
+
+impl TagSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Frontmatter => "frontmatter",
+            Self::Body => "body",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Note {
+    pub frontmatter: Frontmatter,
+    /// Inline `#tags` from the body, only read when enabled.
+    pub body_tags: Vec<String>,
+    /// Dataview inline fields from the body, keyed by their normalized name, only read when
+    /// enabled.
+    pub inline_fields: Vec<(String, FieldValue)>,
+    /// Checklist items from the body, only read when task metrics are enabled.
+    pub tasks: Vec<Task>,
+    /// Counts of words, headings and links in the body, only computed when enabled.
+    pub writing_stats: Option<WritingStats>,
+    pub period: Period,
+    /// First day of the note's period.
+    pub date: NaiveDate,
+    /// Time of the note, when it is dated by a property with a time.
+    pub time: Option<NaiveTime>,
+    /// Path of the note relative to the vault.
+    pub path: PathBuf,
+    /// SHA-256 of the file contents, used to tell whether the note changed since it was pushed.
+    pub hash: String,
+}
+
+/// Notes read from the notes directories.
+pub struct NoteScan {
+    pub notes: Vec<Note>,
+    /// Number of note files found, whether or not they were read.
+    pub files_scanned: usize,
+}
+
+impl Note {
+    /// Tags that are pushed, without their leading `#`, along with where they were found.
+    pub fn sourced_tags(&self) -> impl Iterator<Item = (&str, TagSource)> {
+        let frontmatter = self
+            .frontmatter
+            .tags
+            .iter()
+            .map(|tag| (tag.as_str(), TagSource::Frontmatter));
+        let body = self
+            .body_tags
+            .iter()
+            .map(|tag| (tag.as_str(), TagSource::Body));
+
+        frontmatter.chain(body)
+    }
+
+    /// Tags that are pushed, without their leading `#`. A tag found both in the frontmatter
+    /// and the body is returned twice.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.sourced_tags().map(|(tag, _)| tag)
+    }
+
+    /// Time of the note's points in the configured timezone, as set by the timestamp mode.
+    pub fn timestamp(&self, config: &Config) -> DateTime<Utc> {
+        let time = match config.timestamp_mode {
+            TimestampMode::Midnight => NaiveTime::MIN,
+            TimestampMode::NoteTime | TimestampMode::Hash => self.time.unwrap_or(NaiveTime::MIN),
+        };
+        let timestamp = to_utc(self.date.and_time(time), config.timezone);
+
+        if config.timestamp_mode != TimestampMode::Hash {
+            return timestamp;
+        }
+
+        // The path is relative to the vault, so the offset stays the same wherever it is synced.
+        let digest = Sha256::digest(self.path.to_string_lossy().as_bytes());
+        let offset = u64::from_be_bytes(digest[..8].try_into().unwrap()) % 1_000_000_000;
+
+        timestamp + Duration::nanoseconds(i64::try_from(offset).unwrap())
+    }
+
+    /// Number of the note's distinct tags nested under each of their ancestors.
+    pub fn tag_rollups(&self) -> BTreeMap<&str, u32> {
+        let mut rollups: BTreeMap<&str, u32> = BTreeMap::new();
+
+        self.tags()
+            .collect::<BTreeSet<&str>>()
+            .into_iter()
+            .flat_map(tag_ancestors)
+            .for_each(|ancestor| *rollups.entry(ancestor).or_default() += 1);
+
+        rollups
+    }
+}
+
+fn build_vault_path(path: &str, dir: &str) -> PathBuf {
+    let mut notes_path: PathBuf = PathBuf::from(path);
+
+    notes_path.push(dir);
+
+    println!("Vault path: {:?}", notes_path.as_os_str());
+
+    notes_path
+}
+
+/// Splits a note into its YAML frontmatter, if it starts with one, and its body.
+fn split_frontmatter(contents: &str) -> (Option<&str>, &str) {
+    let mut lines = contents.split_inclusive('\n');
+
+    if lines.next().map(str::trim) != Some("---") {
+        return (None, contents);
+    }
+
+    let yaml_start = contents.len() - lines.clone().map(str::len).sum::<usize>();
+    let mut offset = yaml_start;
+
+    for line in lines {
+        if line.trim() == "---" {
+            return (
+                Some(&contents[yaml_start..offset]),
+                &contents[offset + line.len()..],
+            );
+        }
+
+        offset += line.len();
+    }
+
+    (None, contents)
+}
+
+/// Formats accepted for dates with a time in frontmatter properties, as Obsidian writes them and
+/// as they are commonly typed.
+const DATETIME_PROPERTY_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Reads a date, and its time if it has one, from the first of the properties that holds one.
+/// An offset like `+02:00` is dropped, keeping the time as written.
+fn date_from_properties(
+    frontmatter: &Frontmatter,
+    keys: &[String],
+) -> Option<(NaiveDate, Option<NaiveTime>)> {
+    keys.iter()
+        .filter_map(|key| frontmatter.properties.get(key)?.as_str())
+        .find_map(|value| {
+            let value = value.trim();
+
+            if let Ok(date) = NaiveDate::parse_from_str(value, DATE_PROPERTY_FORMAT) {
+                return Some((date, None));
+            }
+
+            let datetime = DateTime::parse_from_rfc3339(value)
+                .map(|datetime| datetime.naive_local())
+                .ok()
+                .or_else(|| {
+                    DATETIME_PROPERTY_FORMATS
+                        .iter()
+                        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+                })?;
+
+            Some((datetime.date(), Some(datetime.time())))
+        })
+}
+
+/// Reads a note, dated by `name_date` unless it is a daily note with a date property, which
+/// takes precedence. Notes without either are skipped.
+fn note_from_path(
+    path: &Path,
+    config: &Config,
+    vault_path: &Path,
+    period: Period,
+    name_date: Option<NaiveDate>,
+) -> Option<Note> {
+    let file_contents: String = fs::read_to_string(path).ok()?;
+
+    let hash = format!("{:x}", Sha256::digest(file_contents.as_bytes()));
+
+    let (yaml, body) = split_frontmatter(&file_contents);
+
+    let frontmatter: Frontmatter = match yaml {
+        Some(yaml) if !yaml.trim().is_empty() => serde_yaml::from_str(yaml).ok()?,
+        _ => Frontmatter::default(),
+    };
+
+    let body_tags = if config.body_tags {
+        extract_tags(body)
+    } else {
+        Vec::new()
+    };
+
+    let inline_fields = if config.inline_fields {
+        extract_inline_fields(body)
+    } else {
+        Vec::new()
+    };
+
+    let tasks = if config.tasks || config.task_tags {
+        extract_tasks(body)
+    } else {
+        Vec::new()
+    };
+
+    let writing_stats = config.writing_stats.then(|| writing_stats(body));
+
+    let property_date = match period {
+        Period::Daily => date_from_properties(&frontmatter, &config.date_properties),
+        _ => None,
+    };
+
+    let (date, time) = property_date.or(name_date.map(|date| (date, None)))?;
+
+    Some(Note {
+        frontmatter,
+        body_tags,
+        inline_fields,
+        tasks,
+        writing_stats,
+        period,
+        date,
+        time,
+        path: path.strip_prefix(vault_path).unwrap_or(path).to_path_buf(),
+        hash,
+    })
+}
+
+/// Reads the date from the name of a note of the period, which is its file name, or its path
+/// within `notes_path` when the date format spans folders.
+fn date_from_name(path: &Path, notes_path: &Path, notes: &PeriodicNotes) -> Option<NaiveDate> {
+    let name = if notes.date_format.spans_folders() {
+        path.strip_prefix(notes_path)
+            .ok()?
+            .with_extension("")
+            .to_str()?
+            .replace(std::path::MAIN_SEPARATOR, "/")
+    } else {
+        path.file_stem()?.to_str()?.to_string()
+    };
+
+    notes.date_format.date_of(&name)
+}
+
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry
+        .file_name()
+        .to_str()
+        .is_some_and(|s| s.starts_with('.'))
+}
+
+/// Finds the periodic notes in the notes directories, keyed by path, along with the number of
+/// note files found. A file whose name matches the formats of several periods, which can
+/// happen when they share a folder, is taken as a note of the shortest one. When notes can be
+/// dated by a property, every other file in the daily notes directory is a daily note without
+/// a date yet.
+fn find_notes(config: &Config) -> (BTreeMap<PathBuf, (Period, Option<NaiveDate>)>, usize) {
+    let mut found: BTreeMap<PathBuf, (Period, Option<NaiveDate>)> = BTreeMap::new();
+    let mut undated: Vec<PathBuf> = Vec::new();
+    let mut files: BTreeSet<PathBuf> = BTreeSet::new();
+
+    for notes in &config.periodic_notes {
+        let notes_path: PathBuf = build_vault_path(&config.vault_path, &notes.notes_dir);
+
+        println!(
+            "Getting {} notes from dir {:?}",
+            notes.period.as_str(),
+            notes_path.as_os_str()
+        );
+
+        let entries = WalkDir::new(&notes_path)
+            .into_iter()
+            // The root itself may be `./`, which is not hidden.
+            .filter_entry(|e| e.depth() == 0 || !is_hidden(e))
+            .filter_map(Result::ok)
+            .filter(|e| {
+                e.file_name()
+                    .to_str()
+                    .is_some_and(|s| s.ends_with(NOTE_FILE_EXTENSION))
+            });
+
+        for entry in entries {
+            let path = entry.into_path();
+
+            match date_from_name(&path, &notes_path, notes) {
+                Some(date) => {
+                    found
+                        .entry(path.clone())
+                        .or_insert((notes.period, Some(date)));
+                }
+                None if notes.period == Period::Daily && !config.date_properties.is_empty() => {
+                    undated.push(path.clone());
+                }
+                None => {}
+            }
+
+            files.insert(path);
+        }
+    }
+
+    for path in undated {
+        found.entry(path).or_insert((Period::Daily, None));
+    }
+
+    (found, files.len())
+}
+
+/// Reads the periodic notes that overlap `dates`. Notes of a period that isn't over yet (by
+/// yesterday, or the end of `dates` if that is later) are left out, so that they are only
+/// pushed once complete.
+pub fn read_notes(config: &Config, dates: RangeInclusive<NaiveDate>) -> NoteScan {
+    let complete_by = get_yesterday(config.timezone)
+        .map_or(*dates.end(), |yesterday| yesterday.max(*dates.end()));
+    let vault_path = Path::new(&config.vault_path);
+
+    let in_range = |period: Period, date: NaiveDate| {
+        let last_day = period.last_day(date);
+
+        date <= *dates.end() && last_day >= *dates.start() && last_day <= complete_by
+    };
+
+    // Daily notes might be dated by a property, which is only known once they are read.
+    let dated_by_properties = !config.date_properties.is_empty();
+
+    let (found, files_scanned) = find_notes(config);
+
+    let mut notes = found
+        .into_par_iter()
+        .filter(|(_, (period, date))| match (period, date) {
+            (Period::Daily, _) if dated_by_properties => true,
+            (period, Some(date)) => in_range(*period, *date),
+            (_, None) => false,
+        })
+        .filter_map(|(path, (period, date))| {
+            note_from_path(&path, config, vault_path, period, date)
+        })
+        .filter(|note| in_range(note.period, note.date))
+        .collect::<Vec<Note>>();
+
+    notes.sort_by_key(|note| (note.date, note.time, note.period));
+
+    NoteScan {
+        notes,
+        files_scanned,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(yaml: &str) -> Vec<String> {
+        serde_yaml::from_str::<Frontmatter>(yaml).unwrap().tags
+    }
+
+    #[test]
+    fn reads_tags_as_a_list() {
+        assert_eq!(
+            tags("tags: [sleep, '#health/food']"),
+            vec!["sleep", "health/food"]
+        );
+        assert_eq!(tags("tags:\n  - a\n  - 3\n  - ''\n"), vec!["a"]);
+    }
+
+    #[test]
+    fn reads_tags_as_a_string() {
+        assert_eq!(tags("tags: a, b c"), vec!["a", "b", "c"]);
+        assert_eq!(tags("tags: '#a,#b'"), vec!["a", "b"]);
+        assert_eq!(tags("tags: ' , # '"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reads_the_legacy_tag_key() {
+        assert_eq!(tags("tag: legacy"), vec!["legacy"]);
+        assert_eq!(tags("tag: [a, x]\ntags: [x, y]"), vec!["x", "y", "a"]);
+    }
+
+    #[test]
+    fn keeps_other_properties() {
+        let frontmatter: Frontmatter = serde_yaml::from_str("tags: a\nmood: 8\n").unwrap();
+
+        assert_eq!(frontmatter.tags, vec!["a"]);
+        assert_eq!(
+            frontmatter.properties.get("mood"),
+            Some(&Value::Number(8.into()))
+        );
+        assert!(tags("mood: 8").is_empty());
+        assert!(tags("tags: 8").is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Error};
+use serde::Deserialize;
+
+use crate::period::Period;
+
+/// Settings of the core Daily notes plugin, in `.obsidian/daily-notes.json`.
+const DAILY_NOTES_SETTINGS: &str = ".obsidian/daily-notes.json";
+
+/// Settings of the Periodic Notes community plugin, which replaces the core plugin when enabled.
+const PERIODIC_NOTES_SETTINGS: &str = ".obsidian/plugins/periodic-notes/data.json";
+
+/// Where notes of a period are kept and how they are named, as set in Obsidian. Empty values
+/// are left unset, as Obsidian falls back to its defaults for them.
+#[derive(Debug, Default, Deserialize)]
+pub struct NoteSettings {
+    #[serde(default)]
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub format: Option<String>,
+    /// Only set by the Periodic Notes plugin, whose settings are ignored for disabled periods.
+    #[serde(default)]
+    enabled: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PeriodicNotesSettings {
+    #[serde(default)]
+    daily: Option<NoteSettings>,
+    #[serde(default)]
+    weekly: Option<NoteSettings>,
+    #[serde(default)]
+    monthly: Option<NoteSettings>,
+    #[serde(default)]
+    quarterly: Option<NoteSettings>,
+    #[serde(default)]
+    yearly: Option<NoteSettings>,
+}
+
+impl PeriodicNotesSettings {
+    fn take(self, period: Period) -> Option<NoteSettings> {
+        match period {
+            Period::Daily => self.daily,
+            Period::Weekly => self.weekly,
+            Period::Monthly => self.monthly,
+            Period::Quarterly => self.quarterly,
+            Period::Yearly => self.yearly,
+        }
+    }
+}
+
+impl NoteSettings {
+    /// Drops empty values, and the slashes around the folder so that it stays within the vault.
+    fn non_empty(mut self) -> Self {
+        self.folder = self
+            .folder
+            .map(|folder| folder.trim().trim_matches('/').to_string())
+            .filter(|folder| !folder.is_empty());
+        self.format = self.format.filter(|format| !format.trim().is_empty());
+        self
+    }
+}
+
+/// Reads a JSON settings file of the vault, if it exists.
+fn read_settings<T: for<'de> Deserialize<'de>>(
+    vault_path: &Path,
+    file: &str,
+) -> Result<Option<T>, Error> {
+    let path = vault_path.join(file);
+
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents =
+        fs::read_to_string(&path).context(format!("Could not read {:?}", path.as_os_str()))?;
+
+    serde_json::from_str(&contents)
+        .map(Some)
+        .context(format!("Could not parse {:?}", path.as_os_str()))
+}
+
+/// Reads the settings of the vault for notes of the period, from the Periodic Notes plugin if it
+/// has the period enabled, or else, for daily notes, from the core Daily notes plugin. Returns
+/// `None` when the vault doesn't use notes of the period.
+pub fn read_note_settings(
+    vault_path: &Path,
+    period: Period,
+) -> Result<Option<NoteSettings>, Error> {
+    let periodic_notes =
+        read_settings::<PeriodicNotesSettings>(vault_path, PERIODIC_NOTES_SETTINGS)?
+            .and_then(|settings| settings.take(period))
+            .filter(|settings| settings.enabled == Some(true));
+
+    if let Some(settings) = periodic_notes {
+        println!(
+            "Using {} notes settings from {PERIODIC_NOTES_SETTINGS}",
+            period.as_str()
+        );
+
+        return Ok(Some(settings.non_empty()));
+    }
+
+    if period != Period::Daily {
+        return Ok(None);
+    }
+
+    let daily_notes = read_settings::<NoteSettings>(vault_path, DAILY_NOTES_SETTINGS)?;
+
+    if daily_notes.is_some() {
+        println!("Using daily notes settings from {DAILY_NOTES_SETTINGS}");
+    }
+
+    Ok(daily_notes.map(NoteSettings::non_empty))
+}
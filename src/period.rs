@@ -0,0 +1,58 @@
+use chrono::{Days, Months, NaiveDate};
+
+/// Kind of periodic note, written as the `period` Influx tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Period {
+    Daily,
+    Weekly,
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl Period {
+    /// Every period, shortest first.
+    pub const ALL: [Self; 5] = [
+        Self::Daily,
+        Self::Weekly,
+        Self::Monthly,
+        Self::Quarterly,
+        Self::Yearly,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+            Self::Monthly => "monthly",
+            Self::Quarterly => "quarterly",
+            Self::Yearly => "yearly",
+        }
+    }
+
+    /// Obsidian's default format for the names of notes of the period.
+    pub fn default_format(self) -> &'static str {
+        match self {
+            Self::Daily => "YYYY-MM-DD",
+            Self::Weekly => "gggg-[W]ww",
+            Self::Monthly => "YYYY-MM",
+            Self::Quarterly => "YYYY-[Q]Q",
+            Self::Yearly => "YYYY",
+        }
+    }
+
+    /// Last day of the period starting on `start`.
+    pub fn last_day(self, start: NaiveDate) -> NaiveDate {
+        let next_start = match self {
+            Self::Daily => start.checked_add_days(Days::new(1)),
+            Self::Weekly => start.checked_add_days(Days::new(7)),
+            Self::Monthly => start.checked_add_months(Months::new(1)),
+            Self::Quarterly => start.checked_add_months(Months::new(3)),
+            Self::Yearly => start.checked_add_months(Months::new(12)),
+        };
+
+        next_start
+            .and_then(|next_start| next_start.pred_opt())
+            .unwrap_or(NaiveDate::MAX)
+    }
+}
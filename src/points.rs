@@ -0,0 +1,378 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use chrono::{DateTime, Datelike, Utc, Weekday};
+use influxdb::{InfluxDbWriteable, Timestamp, WriteQuery};
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::{
+    body::{FieldValue, TaskStatus},
+    config::Config,
+    notes::{tag_ancestors, Note, TagSource},
+    period::Period,
+};
+
+/// Measurement that numeric frontmatter properties are written to as fields.
+pub const PROPERTIES_MEASUREMENT: &str = "properties";
+
+/// Measurement that Dataview inline fields are written to as fields.
+pub const INLINE_FIELDS_MEASUREMENT: &str = "inline_fields";
+
+/// Measurement that the task counts of notes are written to.
+pub const TASKS_MEASUREMENT: &str = "tasks";
+
+/// Measurement that the task counts of notes are written to per tag found in the tasks.
+pub const TASK_TAGS_MEASUREMENT: &str = "task_tags";
+
+/// Measurement that the writing statistics of notes are written to.
+pub const WRITING_STATS_MEASUREMENT: &str = "writing_stats";
+
+/// Measurement that boolean frontmatter properties are written to as habit points.
+pub const HABITS_MEASUREMENT: &str = "habits";
+
+/// Field keys that Influx reserves for itself and rejects in writes.
+const RESERVED_FIELD_KEYS: [&str; 3] = ["time", "_field", "_measurement"];
+
+/// A tag of a note. Nested tags like `health/sleep/good` are also split into their root
+/// (`health`), parent (`health/sleep`) and depth (3), so they can be aggregated by category.
+#[derive(Debug, Deserialize, InfluxDbWriteable)]
+pub struct DbEntry {
+    pub time: DateTime<Utc>,
+    #[influxdb(tag)]
+    pub weekday: String,
+    /// Period of the note, `daily`, `weekly`, `monthly`, `quarterly` or `yearly`.
+    #[influxdb(tag)]
+    pub period: String,
+    #[influxdb(tag)]
+    pub frontmatter_tag: String,
+    #[influxdb(tag)]
+    pub tag_root: String,
+    #[influxdb(tag)]
+    pub tag_parent: Option<String>,
+    #[influxdb(tag)]
+    pub tag_depth: String,
+    /// Where the tag was found, `frontmatter` or `body`, unset on rollup points.
+    #[influxdb(tag)]
+    pub source: Option<String>,
+    /// Set on rollup points, whose `value` counts the tags nested under `frontmatter_tag`.
+    #[influxdb(tag)]
+    pub rollup: Option<String>,
+    pub value: u32,
+}
+
+impl DbEntry {
+    /// Builds the point for a tag found in `source`, or a rollup point without one.
+    fn new(
+        time: DateTime<Utc>,
+        weekday: Weekday,
+        period: Period,
+        tag: &str,
+        source: Option<TagSource>,
+        value: u32,
+    ) -> Self {
+        let (tag_parent, tag_depth) = match tag_ancestors(tag).last() {
+            Some(parent) => (Some(parent.to_string()), tag_ancestors(tag).count() + 1),
+            None => (None, 1),
+        };
+
+        Self {
+            time,
+            weekday: weekday.to_string(),
+            period: period.as_str().to_string(),
+            frontmatter_tag: tag.to_string(),
+            tag_root: tag.split('/').next().unwrap_or(tag).to_string(),
+            tag_parent,
+            tag_depth: tag_depth.to_string(),
+            source: source.map(|source| source.as_str().to_string()),
+            rollup: source.is_none().then(|| "true".to_string()),
+            value,
+        }
+    }
+}
+
+/// A checkbox property, with `value` 1 when checked and 0 when explicitly unchecked, so
+/// missed days are recorded rather than absent.
+#[derive(Debug, InfluxDbWriteable)]
+pub struct HabitEntry {
+    pub time: DateTime<Utc>,
+    #[influxdb(tag)]
+    pub weekday: String,
+    #[influxdb(tag)]
+    pub period: String,
+    #[influxdb(tag)]
+    pub habit: String,
+    pub value: u8,
+}
+
+/// Number of tasks in each status.
+#[derive(Debug, Default)]
+struct TaskCounts {
+    open: u32,
+    done: u32,
+    cancelled: u32,
+    deferred: u32,
+}
+
+impl TaskCounts {
+    fn add(&mut self, status: TaskStatus) {
+        match status {
+            TaskStatus::Open => self.open += 1,
+            TaskStatus::Done => self.done += 1,
+            TaskStatus::Cancelled => self.cancelled += 1,
+            TaskStatus::Deferred => self.deferred += 1,
+        }
+    }
+
+    /// Adds the counts as fields, along with the share of tasks that were done out of those
+    /// that weren't cancelled, left out when every task was cancelled.
+    fn add_fields(&self, insert: WriteQuery) -> WriteQuery {
+        let total = self.open + self.done + self.cancelled + self.deferred;
+
+        let insert = insert
+            .add_field("total", total)
+            .add_field("open", self.open)
+            .add_field("done", self.done)
+            .add_field("cancelled", self.cancelled)
+            .add_field("deferred", self.deferred);
+
+        match total - self.cancelled {
+            0 => insert,
+            countable => insert.add_field(
+                "completion_rate",
+                f64::from(self.done) / f64::from(countable),
+            ),
+        }
+    }
+}
+
+/// Every measurement that points are written to for a note, so that they can all be cleared
+/// when the note is pushed again.
+pub fn note_measurements(config: &Config) -> Vec<&str> {
+    let mut measurements = vec![config.measurement.as_str()];
+
+    if config.numeric_properties.is_enabled() {
+        measurements.push(PROPERTIES_MEASUREMENT);
+    }
+
+    if config.boolean_properties.is_enabled() {
+        measurements.push(HABITS_MEASUREMENT);
+    }
+
+    if config.inline_fields {
+        measurements.push(INLINE_FIELDS_MEASUREMENT);
+    }
+
+    if config.tasks {
+        measurements.push(TASKS_MEASUREMENT);
+    }
+
+    if config.task_tags {
+        measurements.push(TASK_TAGS_MEASUREMENT);
+    }
+
+    if config.writing_stats {
+        measurements.push(WRITING_STATS_MEASUREMENT);
+    }
+
+    measurements
+}
+
+/// Starts a point at the note's time, tagged with its weekday and period.
+fn note_query(note: &Note, measurement: &str, config: &Config) -> WriteQuery {
+    Timestamp::from(note.timestamp(config))
+        .into_query(measurement)
+        .add_tag("weekday", note.date.weekday().to_string())
+        .add_tag("period", note.period.as_str())
+}
+
+fn build_tag_inserts<'a>(
+    note: &'a Note,
+    config: &'a Config,
+) -> impl Iterator<Item = WriteQuery> + 'a {
+    let note_time = note.timestamp(config);
+    let weekday = note.date.weekday();
+    let measurement = config.measurement.as_str();
+
+    // Every tag and rollup is its own series, so they can all sit at the note's time.
+    let tags = note.sourced_tags().map(move |(tag, source)| {
+        DbEntry::new(note_time, weekday, note.period, tag, Some(source), 1).into_query(measurement)
+    });
+
+    let rollups = if config.tag_rollups {
+        note.tag_rollups()
+    } else {
+        BTreeMap::new()
+    };
+
+    let rollups = rollups.into_iter().map(move |(ancestor, count)| {
+        DbEntry::new(note_time, weekday, note.period, ancestor, None, count).into_query(measurement)
+    });
+
+    tags.chain(rollups)
+}
+
+/// Returns the field key for a property or inline field, with a `_value` suffix on the keys
+/// Influx reserves, so that a field like `time:: 20` is written rather than failing the write.
+fn field_key(key: &str) -> Cow<'_, str> {
+    if RESERVED_FIELD_KEYS.contains(&key) {
+        Cow::Owned(format!("{key}_value"))
+    } else {
+        Cow::Borrowed(key)
+    }
+}
+
+/// Builds a single point with every selected numeric property of the note as a field.
+/// Numbers are always written as floats, so a property that is sometimes written as an
+/// integer does not conflict with the field's type.
+fn build_properties_insert(note: &Note, config: &Config) -> Option<WriteQuery> {
+    let fields: Vec<(&str, f64)> = note
+        .frontmatter
+        .properties
+        .iter()
+        .filter(|(key, _)| config.numeric_properties.contains(key))
+        .filter_map(|(key, value)| match value {
+            Value::Number(number) => number.as_f64().map(|number| (key.as_str(), number)),
+            _ => None,
+        })
+        .collect();
+
+    if fields.is_empty() {
+        return None;
+    }
+
+    let insert = fields.into_iter().fold(
+        note_query(note, PROPERTIES_MEASUREMENT, config),
+        |insert, (key, value)| insert.add_field(field_key(key), value),
+    );
+
+    Some(insert)
+}
+
+/// Builds a single point with every inline field of the note as a field of its own type.
+/// Numbers and durations, in seconds, are written as floats under the key itself, while
+/// booleans and text get a `_bool` or `_text` suffix. Influx rejects writes that change a
+/// field's type, so a key that holds a number in one note and text in another must not share
+/// a field.
+fn build_inline_fields_insert(note: &Note, config: &Config) -> Option<WriteQuery> {
+    if note.inline_fields.is_empty() {
+        return None;
+    }
+
+    let insert = note.inline_fields.iter().fold(
+        note_query(note, INLINE_FIELDS_MEASUREMENT, config),
+        |insert, (key, value)| match value {
+            FieldValue::Number(number) | FieldValue::Duration(number) => {
+                insert.add_field(field_key(key), *number)
+            }
+            FieldValue::Bool(value) => insert.add_field(format!("{key}_bool"), *value),
+            FieldValue::Text(text) => insert.add_field(format!("{key}_text"), text.as_str()),
+        },
+    );
+
+    Some(insert)
+}
+
+/// Builds the task counts of the note, and its counts per tag found in tasks, as enabled.
+/// Notes without tasks have no points, rather than points with zero counts.
+fn build_task_inserts(note: &Note, config: &Config) -> Vec<WriteQuery> {
+    let mut inserts: Vec<WriteQuery> = Vec::new();
+
+    if config.tasks && !note.tasks.is_empty() {
+        let mut counts = TaskCounts::default();
+
+        note.tasks.iter().for_each(|task| counts.add(task.status));
+
+        inserts.push(counts.add_fields(note_query(note, TASKS_MEASUREMENT, config)));
+    }
+
+    if config.task_tags {
+        let mut counts_per_tag: BTreeMap<&str, TaskCounts> = BTreeMap::new();
+
+        for task in &note.tasks {
+            for tag in &task.tags {
+                counts_per_tag.entry(tag).or_default().add(task.status);
+            }
+        }
+
+        inserts.extend(counts_per_tag.into_iter().map(|(tag, counts)| {
+            counts.add_fields(
+                note_query(note, TASK_TAGS_MEASUREMENT, config).add_tag("task_tag", tag),
+            )
+        }));
+    }
+
+    inserts
+}
+
+fn build_writing_stats_insert(note: &Note, config: &Config) -> Option<WriteQuery> {
+    let stats = note.writing_stats.as_ref()?;
+    let insert = note_query(note, WRITING_STATS_MEASUREMENT, config)
+        .add_field("words", stats.words)
+        .add_field("characters", stats.characters)
+        .add_field("headings", stats.headings)
+        .add_field("wikilinks", stats.wikilinks)
+        .add_field("embeds", stats.embeds)
+        .add_field("external_links", stats.external_links);
+
+    Some(insert)
+}
+
+fn build_habit_inserts<'a>(
+    note: &'a Note,
+    config: &'a Config,
+) -> impl Iterator<Item = WriteQuery> + 'a {
+    let time = note.timestamp(config);
+    let weekday = note.date.weekday();
+
+    note.frontmatter
+        .properties
+        .iter()
+        .filter(|(key, _)| config.boolean_properties.contains(key))
+        .filter_map(move |(key, value)| match value {
+            Value::Bool(checked) => Some(
+                HabitEntry {
+                    time,
+                    weekday: weekday.to_string(),
+                    period: note.period.as_str().to_string(),
+                    habit: key.clone(),
+                    value: u8::from(*checked),
+                }
+                .into_query(HABITS_MEASUREMENT),
+            ),
+            _ => None,
+        })
+}
+
+/// Builds every point to be written for the notes.
+pub fn build_inserts(notes: &[Note], config: &Config) -> Vec<WriteQuery> {
+    notes
+        .iter()
+        .flat_map(|note| {
+            build_tag_inserts(note, config)
+                .chain(build_properties_insert(note, config))
+                .chain(build_habit_inserts(note, config))
+                .chain(build_inline_fields_insert(note, config))
+                .chain(build_task_inserts(note, config))
+                .chain(build_writing_stats_insert(note, config))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_reserved_field_keys() {
+        assert_eq!(field_key("time"), "time_value");
+        assert_eq!(field_key("_field"), "_field_value");
+        assert_eq!(field_key("_measurement"), "_measurement_value");
+    }
+
+    #[test]
+    fn keeps_other_field_keys() {
+        assert_eq!(field_key("weight"), "weight");
+        assert_eq!(field_key("time-spent"), "time-spent");
+        assert_eq!(field_key("Time"), "Time");
+    }
+}
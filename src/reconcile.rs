@@ -0,0 +1,127 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Error;
+use chrono::NaiveDate;
+
+use crate::{
+    config::Config,
+    influx::{Database, StoredTag},
+    notes::{read_notes, tag_ancestors, Note},
+    timezone::{day_range, day_start, get_yesterday, local_date},
+};
+
+/// Tags of the notes of a day, to tell which of the points stored for it are stale.
+#[derive(Default)]
+struct CurrentTags<'a> {
+    /// Tags along with where they were found.
+    sourced: BTreeSet<(&'a str, &'static str)>,
+    /// Names of the tags, and of their ancestors when rollups are written.
+    names: BTreeSet<&'a str>,
+}
+
+impl CurrentTags<'_> {
+    fn contains(&self, stored: &StoredTag) -> bool {
+        let tag = stored.frontmatter_tag.as_str();
+
+        match &stored.source {
+            Some(source) => self.sourced.contains(&(tag, source.as_str())),
+            // Rollups, and points written before sources were recorded, only have a name.
+            None => self.names.contains(tag),
+        }
+    }
+}
+
+/// Removes points for tags that are no longer in their note's frontmatter.
+///
+/// Only days with a note that could be parsed are compared, so a note that fails to parse
+/// does not lose all of its points.
+pub async fn reconcile_notes(config: &Config, database: &Database) -> Result<(), Error> {
+    println!("Reconciling tags...");
+
+    let notes: Vec<Note> =
+        read_notes(config, NaiveDate::MIN..=get_yesterday(config.timezone)?).notes;
+
+    let (Some(first), Some(last)) = (notes.first(), notes.last()) else {
+        println!("No notes were found");
+        return Ok(());
+    };
+
+    let start = day_start(first.date, config.timezone);
+    let stop = day_range(last.date, config.timezone).1;
+
+    // Rollups are stored under their ancestor's name, so those are current too. Notes of
+    // different periods can start on the same day, so their tags are kept apart by period.
+    let mut current_tags: BTreeMap<NaiveDate, BTreeMap<&str, CurrentTags>> = BTreeMap::new();
+
+    for note in &notes {
+        let tags = current_tags
+            .entry(note.date)
+            .or_default()
+            .entry(note.period.as_str())
+            .or_default();
+
+        tags.sourced.extend(
+            note.sourced_tags()
+                .map(|(tag, source)| (tag, source.as_str())),
+        );
+        tags.names.extend(note.tags());
+
+        if config.tag_rollups {
+            tags.names.extend(note.tags().flat_map(tag_ancestors));
+        }
+    }
+
+    let stale_tags: BTreeSet<(NaiveDate, String, Option<String>, Option<String>)> = database
+        .get_stored_tags(&config.measurement, start, stop)
+        .await?
+        .into_iter()
+        .filter_map(|stored| {
+            let date = local_date(stored.time, config.timezone);
+            let periods = current_tags.get(&date)?;
+
+            let is_current = match stored.period.as_deref() {
+                Some(period) => periods.get(period)?.contains(&stored),
+                // Points written before periods were recorded can be from any note of the day.
+                None => periods.values().any(|tags| tags.contains(&stored)),
+            };
+
+            (!is_current).then_some((date, stored.frontmatter_tag, stored.source, stored.period))
+        })
+        .collect();
+
+    for (date, tag, source, period) in &stale_tags {
+        let (start, stop) = day_range(*date, config.timezone);
+        let mut tags = vec![("frontmatter_tag", tag.as_str())];
+
+        // A tag removed from the frontmatter may still be in the body, and one removed from a
+        // daily note may still be in the weekly note starting that day, so only the series it
+        // was removed from is deleted.
+        if let Some(source) = source {
+            tags.push(("source", source.as_str()));
+        }
+
+        if let Some(period) = period {
+            tags.push(("period", period.as_str()));
+        }
+
+        database
+            .delete_points(&config.measurement, start, stop, &tags)
+            .await?;
+
+        let series: Vec<&str> = tags[1..].iter().map(|(_, value)| *value).collect();
+
+        if series.is_empty() {
+            println!("Removed {tag} from {date}");
+        } else {
+            println!("Removed {tag} ({}) from {date}", series.join(", "));
+        }
+    }
+
+    println!(
+        "Reconciled {} notes, removed {} stale tags",
+        notes.len(),
+        stale_tags.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Error};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::notes::Note;
+
+/// What was last pushed for a single note.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NoteState {
+    pub date: NaiveDate,
+    pub hash: String,
+    pub pushed_at: DateTime<Utc>,
+}
+
+/// Local record of the notes that have been pushed, keyed by their path relative to the vault.
+/// Lets runs resume without asking the database where the previous one stopped.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SyncState {
+    #[serde(default)]
+    pub notes: BTreeMap<PathBuf, NoteState>,
+}
+
+impl SyncState {
+    /// Loads the state file, starting from an empty state if it does not exist yet.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        if !path.exists() {
+            println!(
+                "State file {:?} not found, starting fresh",
+                path.as_os_str()
+            );
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)
+            .context(format!("Could not read state file {:?}", path.as_os_str()))?;
+
+        serde_json::from_str(&contents)
+            .context(format!("Could not parse state file {:?}", path.as_os_str()))
+    }
+
+    /// Saves the state through a temporary file so an interrupted run cannot leave it truncated.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let contents = serde_json::to_string_pretty(self)?;
+        let tmp_path = path.with_extension("tmp");
+
+        fs::write(&tmp_path, contents).context(format!(
+            "Could not write state file {:?}",
+            tmp_path.as_os_str()
+        ))?;
+        fs::rename(&tmp_path, path).context(format!(
+            "Could not replace state file {:?}",
+            path.as_os_str()
+        ))?;
+
+        Ok(())
+    }
+
+    /// Whether the note was pushed at some point, regardless of its contents since.
+    pub fn was_pushed(&self, note: &Note) -> bool {
+        self.notes.contains_key(&note.path)
+    }
+
+    /// The date the note was last pushed with, if it has been dated differently since.
+    pub fn previous_date(&self, note: &Note) -> Option<NaiveDate> {
+        self.notes
+            .get(&note.path)
+            .map(|pushed| pushed.date)
+            .filter(|date| *date != note.date)
+    }
+
+    /// Whether the note is new or its contents differ from when it was last pushed.
+    pub fn is_changed(&self, note: &Note) -> bool {
+        !matches!(self.notes.get(&note.path), Some(pushed) if pushed.hash == note.hash)
+    }
+
+    pub fn record(&mut self, note: &Note, pushed_at: DateTime<Utc>) {
+        self.notes.insert(
+            note.path.clone(),
+            NoteState {
+                date: note.date,
+                hash: note.hash.clone(),
+                pushed_at,
+            },
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+
+use anyhow::{Context, Error};
+use chrono::NaiveDate;
+
+use crate::{
+    config::{Config, DbTarget},
+    influx::Database,
+    notes::read_notes,
+    state::SyncState,
+    timezone::get_yesterday,
+};
+
+/// Prints where the notes are pushed to and how far the previous runs got.
+pub async fn show_status(config: &Config, database: &Database) -> Result<(), Error> {
+    match &config.db_target {
+        DbTarget::V1 { db_name, .. } => println!("Target: InfluxDB 1.x database {db_name}"),
+        DbTarget::V2 { org, bucket, .. } => {
+            println!("Target: InfluxDB 2.x bucket {bucket} in org {org}");
+        }
+    }
+
+    println!("URL: {}", config.db_url());
+    println!("Measurement: {}", config.measurement);
+
+    match database.get_latest_date(&config.measurement).await {
+        Ok(Some(time)) => println!("Latest point: {time}"),
+        Ok(None) => println!("Latest point: none"),
+        Err(e) => println!("Latest point: could not be read ({e})"),
+    }
+
+    let Some(state_file) = config.state_file.as_deref() else {
+        println!("State file: not configured");
+        return Ok(());
+    };
+
+    let state = SyncState::load(state_file)?;
+
+    println!("State file: {:?}", state_file.as_os_str());
+    println!("Notes pushed: {}", state.notes.len());
+
+    if let Some(date) = state.notes.values().map(|note| note.date).max() {
+        println!("Latest note pushed: {date}");
+    }
+
+    if let Some(pushed_at) = state.notes.values().map(|note| note.pushed_at).max() {
+        println!("Last push: {pushed_at}");
+    }
+
+    let pending = read_notes(config, NaiveDate::MIN..=get_yesterday(config.timezone)?)
+        .notes
+        .iter()
+        .filter(|note| state.is_changed(note))
+        .count();
+
+    println!("Notes new or edited since: {pending}");
+
+    Ok(())
+}
+
+/// Deletes the state file, so the next sync pushes every note again.
+pub fn reset_state(config: &Config) -> Result<(), Error> {
+    match config.state_file.as_deref() {
+        Some(state_file) if state_file.exists() => {
+            fs::remove_file(state_file).context(format!(
+                "Could not delete state file {:?}",
+                state_file.as_os_str()
+            ))?;
+
+            println!("Deleted state file {:?}", state_file.as_os_str());
+        }
+        Some(state_file) => println!("State file {:?} does not exist", state_file.as_os_str()),
+        None => println!("No state file configured, nothing to reset"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,381 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem,
+    ops::RangeInclusive,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{anyhow, Context, Error};
+use chrono::{naive::NaiveDate, DateTime, Utc};
+use influxdb::{Query, WriteQuery};
+
+use crate::{
+    config::Config,
+    influx::Database,
+    notes::{read_notes, Note},
+    period::Period,
+    points::{build_inserts, note_measurements},
+    state::SyncState,
+    timezone::{day_range, day_start, get_yesterday, local_date},
+};
+
+/// Which notes a run pushes.
+pub enum Selection {
+    /// Notes that are new since the last run, or edited when there is a state file.
+    Pending,
+    /// Every note in the date range, whether it was pushed before or not.
+    Range {
+        dates: RangeInclusive<NaiveDate>,
+        existing: ExistingPoints,
+    },
+}
+
+/// What to do with points already in the database for a backfilled range.
+pub enum ExistingPoints {
+    /// Write alongside them, replacing points with the same series and time.
+    Keep,
+    /// Delete every point in the range before writing.
+    Overwrite,
+    /// Leave days that already have points untouched.
+    Skip,
+}
+
+/// Works out the date after which notes are read. With a state file every note is read and
+/// compared against it, so edited notes are picked up too. Otherwise the newest point in the
+/// database is used, and a failed read is an error rather than a reason to push every note again.
+async fn get_starting_date(
+    config: &Config,
+    database: &Database,
+    state: Option<&SyncState>,
+) -> Result<NaiveDate, Error> {
+    if state.is_some() {
+        println!("Comparing all notes against the state file...");
+
+        return Ok(NaiveDate::MIN);
+    }
+
+    println!("Reading latest entry from InfluxDB...");
+
+    let latest_date = database
+        .get_latest_date(&config.measurement)
+        .await
+        .context("Could not get date from latest entry")?
+        .map(|time| local_date(time, config.timezone));
+
+    let starting_date = latest_date.unwrap_or_else(|| {
+        println!("No previous entries found, pushing all notes");
+        DateTime::<Utc>::from(UNIX_EPOCH).date_naive()
+    });
+
+    println!("Using {starting_date} as starting point...");
+
+    Ok(starting_date)
+}
+
+/// Notes of whole days whose points are written together, and recorded in the state file once
+/// written, so that a run that fails part way resumes after the last batch it wrote.
+struct Batch<'a> {
+    notes: &'a [Note],
+    inserts: Vec<WriteQuery>,
+}
+
+/// Splits the notes, sorted by date, into batches of about `batch_size` points. A day's notes
+/// are never split, as its points are replaced together, so a day with more points than that
+/// makes up a batch of its own.
+fn batch_notes<'a>(notes: &'a [Note], config: &Config) -> Vec<Batch<'a>> {
+    let mut batches: Vec<Batch> = Vec::new();
+    let mut inserts: Vec<WriteQuery> = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+
+    for day in notes.chunk_by(|a, b| a.date == b.date) {
+        let day_inserts = build_inserts(day, config);
+
+        if end > start && inserts.len() + day_inserts.len() > config.batch_size {
+            batches.push(Batch {
+                notes: &notes[start..end],
+                inserts: mem::take(&mut inserts),
+            });
+            start = end;
+        }
+
+        inserts.extend(day_inserts);
+        end += day.len();
+    }
+
+    if end > start {
+        batches.push(Batch {
+            notes: &notes[start..end],
+            inserts,
+        });
+    }
+
+    batches
+}
+
+/// Returns the days to clear before writing `notes`, a batch of the notes of the run, so that
+/// old points are replaced rather than left alongside the new ones: the days of the notes that
+/// were pushed before, and the days that notes of the run were previously pushed on. A day that
+/// notes of the run are written to is cleared by the batch of those notes, so that a later batch
+/// doesn't delete what it wrote.
+fn get_replaced_days(
+    notes: &[Note],
+    run: &[Note],
+    state: Option<&SyncState>,
+) -> BTreeSet<(NaiveDate, Period)> {
+    let Some(state) = state else {
+        return BTreeSet::new();
+    };
+
+    let written: BTreeSet<(NaiveDate, Period)> =
+        run.iter().map(|note| (note.date, note.period)).collect();
+    let moved_from: BTreeSet<(NaiveDate, Period)> = run
+        .iter()
+        .filter_map(|note| Some((state.previous_date(note)?, note.period)))
+        .collect();
+
+    notes
+        .iter()
+        .flat_map(|note| {
+            let day = (note.date, note.period);
+            let current = (state.was_pushed(note) || moved_from.contains(&day)).then_some(day);
+            let previous = state
+                .previous_date(note)
+                .map(|date| (date, note.period))
+                .filter(|day| !written.contains(day));
+
+            current.into_iter().chain(previous)
+        })
+        .collect()
+}
+
+/// Returns the days in `dates` that already have points in the database.
+async fn get_dates_with_points(
+    config: &Config,
+    database: &Database,
+    dates: &RangeInclusive<NaiveDate>,
+) -> Result<BTreeSet<NaiveDate>, Error> {
+    Ok(database
+        .get_stored_tags(
+            &config.measurement,
+            day_start(*dates.start(), config.timezone),
+            day_range(*dates.end(), config.timezone).1,
+        )
+        .await?
+        .into_iter()
+        .map(|stored| local_date(stored.time, config.timezone))
+        .collect())
+}
+
+/// Prints the line protocol that would be written, followed by a summary of the run.
+fn print_dry_run(
+    notes: &[Note],
+    files_scanned: usize,
+    replaced_days: usize,
+    batches: &[Batch],
+) -> Result<(), Error> {
+    println!("Dry run, nothing will be written. Points:");
+
+    let inserts: Vec<&WriteQuery> = batches.iter().flat_map(|batch| &batch.inserts).collect();
+
+    for insert in &inserts {
+        println!("{}", insert.build()?.get());
+    }
+
+    let mut points_per_tag: BTreeMap<&str, usize> = BTreeMap::new();
+
+    notes
+        .iter()
+        .flat_map(Note::tags)
+        .for_each(|tag| *points_per_tag.entry(tag).or_default() += 1);
+
+    println!();
+    println!("Notes scanned: {files_scanned}");
+    println!("Notes matched: {}", notes.len());
+    println!("Days with edited notes: {replaced_days}");
+    println!("Points: {}", inserts.len());
+    println!("Batches: {}", batches.len());
+    println!("Points per tag:");
+
+    for (tag, count) in points_per_tag {
+        println!("  {tag}: {count}");
+    }
+
+    Ok(())
+}
+
+/// Reads the selected notes and writes their tags to the database, recording them in the state
+/// file if there is one. With `dry_run` nothing is written or recorded.
+pub async fn push_notes_data(
+    config: &Config,
+    database: &Database,
+    selection: Selection,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let mut state: Option<SyncState> = config
+        .state_file
+        .as_deref()
+        .map(SyncState::load)
+        .transpose()?;
+
+    let mut overwrite_dates: Option<RangeInclusive<NaiveDate>> = None;
+
+    let (notes, files_scanned): (Vec<Note>, usize) = match selection {
+        Selection::Pending => {
+            let starting_date: NaiveDate =
+                get_starting_date(config, database, state.as_ref()).await?;
+
+            println!("Adding notes...");
+
+            let scan = read_notes(
+                config,
+                starting_date.succ_opt().unwrap_or(starting_date)..=get_yesterday(config.timezone)?,
+            );
+
+            // Points are replaced a day at a time, so the other notes of a changed note's day
+            // and period, and of the day it was pushed on before if it was dated differently,
+            // are pushed again along with it.
+            let notes = match &state {
+                Some(state) => {
+                    let changed_days: BTreeSet<(NaiveDate, Period)> = scan
+                        .notes
+                        .iter()
+                        .filter(|note| state.is_changed(note))
+                        .flat_map(|note| {
+                            let previous = state.previous_date(note);
+
+                            [Some(note.date), previous]
+                                .into_iter()
+                                .flatten()
+                                .map(|date| (date, note.period))
+                        })
+                        .collect();
+
+                    scan.notes
+                        .into_iter()
+                        .filter(|note| changed_days.contains(&(note.date, note.period)))
+                        .collect()
+                }
+                None => scan.notes,
+            };
+
+            (notes, scan.files_scanned)
+        }
+        Selection::Range { dates, existing } => {
+            println!("Adding notes from {} to {}...", dates.start(), dates.end());
+
+            let scan = read_notes(config, dates.clone());
+
+            let notes = match existing {
+                ExistingPoints::Keep => scan.notes,
+                ExistingPoints::Overwrite => {
+                    overwrite_dates = Some(dates);
+                    scan.notes
+                }
+                ExistingPoints::Skip => {
+                    let existing_dates = get_dates_with_points(config, database, &dates).await?;
+
+                    scan.notes
+                        .into_iter()
+                        .filter(|note| {
+                            let exists = existing_dates.contains(&note.date);
+
+                            if exists {
+                                println!("Skipping {}, points already exist", note.date);
+                            }
+
+                            !exists
+                        })
+                        .collect()
+                }
+            };
+
+            (notes, scan.files_scanned)
+        }
+    };
+
+    // An overwritten range is cleared anyway, so no days need replacing within it.
+    let overwriting = overwrite_dates.is_some();
+
+    let batches: Vec<Batch> = batch_notes(&notes, config);
+
+    if dry_run {
+        let replaced_days =
+            get_replaced_days(&notes, &notes, state.as_ref().filter(|_| !overwriting)).len();
+
+        return print_dry_run(&notes, files_scanned, replaced_days, &batches);
+    }
+
+    if notes.is_empty() {
+        println!("No notes were found");
+        return Ok(());
+    }
+
+    if batches.iter().all(|batch| batch.inserts.is_empty()) {
+        return Err(anyhow!(
+            "Notes were found, but no insert queries were generated"
+        ));
+    }
+
+    if let Some(dates) = overwrite_dates {
+        println!(
+            "Deleting existing points from {} to {}...",
+            dates.start(),
+            dates.end()
+        );
+
+        for measurement in note_measurements(config) {
+            database
+                .delete_points(
+                    measurement,
+                    day_start(*dates.start(), config.timezone),
+                    day_range(*dates.end(), config.timezone).1,
+                    &[],
+                )
+                .await?;
+        }
+    }
+
+    for (index, batch) in batches.iter().enumerate() {
+        for (date, period) in
+            get_replaced_days(batch.notes, &notes, state.as_ref().filter(|_| !overwriting))
+        {
+            println!(
+                "Replacing points for edited {} notes on {date}...",
+                period.as_str()
+            );
+
+            for measurement in note_measurements(config) {
+                database
+                    .delete_day(measurement, date, period, config.timezone)
+                    .await?;
+            }
+        }
+
+        println!(
+            "Writing batch {}/{}, {} points...",
+            index + 1,
+            batches.len(),
+            batch.inserts.len()
+        );
+
+        for inserts in batch.inserts.chunks(config.batch_size) {
+            database.write(inserts, config.write_retries).await?;
+        }
+
+        if let (Some(state), Some(state_file)) = (state.as_mut(), config.state_file.as_deref()) {
+            let pushed_at = Utc::now();
+
+            batch
+                .notes
+                .iter()
+                .for_each(|note| state.record(note, pushed_at));
+
+            state.save(state_file)?;
+        }
+    }
+
+    println!("Finished!");
+
+    Ok(())
+}
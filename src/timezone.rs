@@ -0,0 +1,46 @@
+use anyhow::{Context, Error};
+use chrono::{DateTime, Days, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Converts a local date and time to UTC. A time that happens twice, when clocks go back, is
+/// taken the first time, and a time skipped when clocks go forward is moved past the gap.
+pub fn to_utc(datetime: NaiveDateTime, timezone: Tz) -> DateTime<Utc> {
+    let mut local = datetime;
+
+    // Gaps are at most a few hours long, so stepping through them is quick.
+    loop {
+        match timezone.from_local_datetime(&local) {
+            LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => {
+                return time.with_timezone(&Utc)
+            }
+            LocalResult::None => local += chrono::Duration::minutes(15),
+        }
+    }
+}
+
+/// Start of the day in the timezone, which is usually midnight but can be later on days the
+/// clocks go forward at midnight.
+pub fn day_start(date: NaiveDate, timezone: Tz) -> DateTime<Utc> {
+    to_utc(date.and_time(NaiveTime::MIN), timezone)
+}
+
+/// Start and end of the day in the timezone, which is not always 24 hours long.
+pub fn day_range(date: NaiveDate, timezone: Tz) -> (DateTime<Utc>, DateTime<Utc>) {
+    let next_day = date
+        .checked_add_days(Days::new(1))
+        .unwrap_or(NaiveDate::MAX);
+
+    (day_start(date, timezone), day_start(next_day, timezone))
+}
+
+/// Date in the timezone at the given time.
+pub fn local_date(time: DateTime<Utc>, timezone: Tz) -> NaiveDate {
+    time.with_timezone(&timezone).date_naive()
+}
+
+/// The previous day in the timezone, the last one whose notes are complete.
+pub fn get_yesterday(timezone: Tz) -> Result<NaiveDate, Error> {
+    local_date(Utc::now(), timezone)
+        .pred_opt()
+        .context("Could not get date for yesterday")
+}